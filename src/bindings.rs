@@ -0,0 +1,266 @@
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+/// Key that exits the viewer while control is held, which cannot be bound to anything else
+pub const EXIT_KEY: VirtualKeyCode = VirtualKeyCode::C;
+
+/// Something a key can be bound to in the viewer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveDown,
+    MoveUp,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight,
+    RollLeft,
+    RollRight,
+    IncreaseFocalLength,
+    DecreaseFocalLength,
+    DollyIn,
+    DollyOut,
+    CycleAov,
+    CycleVisualization,
+    Screenshot,
+    ToggleOrbit,
+    ReleaseCursor,
+    ToggleHelp,
+}
+
+impl Action {
+    /// Every action, in the order they are listed in the help
+    pub const ALL: [Self; 22] = [
+        Self::MoveForward,
+        Self::MoveBackward,
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::MoveDown,
+        Self::MoveUp,
+        Self::LookUp,
+        Self::LookDown,
+        Self::LookLeft,
+        Self::LookRight,
+        Self::RollLeft,
+        Self::RollRight,
+        Self::IncreaseFocalLength,
+        Self::DecreaseFocalLength,
+        Self::DollyIn,
+        Self::DollyOut,
+        Self::CycleAov,
+        Self::CycleVisualization,
+        Self::Screenshot,
+        Self::ToggleOrbit,
+        Self::ReleaseCursor,
+        Self::ToggleHelp,
+    ];
+
+    /// Key the action is bound to unless the config file says otherwise
+    const fn default_key(self) -> VirtualKeyCode {
+        match self {
+            Self::MoveForward => VirtualKeyCode::W,
+            Self::MoveBackward => VirtualKeyCode::S,
+            Self::MoveLeft => VirtualKeyCode::A,
+            Self::MoveRight => VirtualKeyCode::D,
+            Self::MoveDown => VirtualKeyCode::Q,
+            Self::MoveUp => VirtualKeyCode::E,
+            Self::LookUp => VirtualKeyCode::I,
+            Self::LookDown => VirtualKeyCode::K,
+            Self::LookLeft => VirtualKeyCode::J,
+            Self::LookRight => VirtualKeyCode::L,
+            Self::RollLeft => VirtualKeyCode::U,
+            Self::RollRight => VirtualKeyCode::O,
+            Self::IncreaseFocalLength => VirtualKeyCode::R,
+            Self::DecreaseFocalLength => VirtualKeyCode::F,
+            Self::DollyIn => VirtualKeyCode::Z,
+            Self::DollyOut => VirtualKeyCode::X,
+            Self::CycleAov => VirtualKeyCode::Tab,
+            Self::CycleVisualization => VirtualKeyCode::V,
+            Self::Screenshot => VirtualKeyCode::P,
+            Self::ToggleOrbit => VirtualKeyCode::T,
+            Self::ReleaseCursor => VirtualKeyCode::Escape,
+            Self::ToggleHelp => VirtualKeyCode::H,
+        }
+    }
+
+    /// What the action does, as shown in the help
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::MoveForward => "Move forward, or in while orbiting",
+            Self::MoveBackward => "Move backward, or out while orbiting",
+            Self::MoveLeft => "Move left, or circle left while orbiting",
+            Self::MoveRight => "Move right, or circle right while orbiting",
+            Self::MoveDown => "Move down, or tilt below while orbiting",
+            Self::MoveUp => "Move up, or tilt above while orbiting",
+            Self::LookUp => "Look up",
+            Self::LookDown => "Look down",
+            Self::LookLeft => "Look left",
+            Self::LookRight => "Look right",
+            Self::RollLeft => "Roll left",
+            Self::RollRight => "Roll right",
+            Self::IncreaseFocalLength => "Increase focal length",
+            Self::DecreaseFocalLength => "Decrease focal length",
+            Self::DollyIn => "Dolly zoom in",
+            Self::DollyOut => "Dolly zoom out",
+            Self::CycleAov => "Cycle auxiliary buffers",
+            Self::CycleVisualization => "Cycle diagnostic views",
+            Self::Screenshot => "Save a screenshot, with shift at high resolution",
+            Self::ToggleOrbit => "Switch between flying and orbiting",
+            Self::ReleaseCursor => "Release the cursor",
+            Self::ToggleHelp => "Show or hide this help",
+        }
+    }
+}
+
+/// Keys the actions of the viewer are bound to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bindings {
+    keys: HashMap<Action, VirtualKeyCode>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            keys: Action::ALL
+                .iter()
+                .map(|&action| (action, action.default_key()))
+                .collect(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Read a RON map from actions to key names, like `{ MoveForward: "W" }`. Actions left out
+    /// keep their default keys.
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let names: HashMap<Action, String> =
+            ron::from_str(src).map_err(|err| format!("invalid key bindings: {err}"))?;
+        let mut bindings = Self::default();
+        for (action, name) in names {
+            let key = key_from_name(&name).ok_or_else(|| format!("unknown key `{name}`"))?;
+            bindings.keys.insert(action, key);
+        }
+
+        for (i, &a) in Action::ALL.iter().enumerate() {
+            if let Some(&b) = Action::ALL[i + 1..]
+                .iter()
+                .find(|&&b| bindings.key(a) == bindings.key(b))
+            {
+                return Err(format!(
+                    "{a:?} and {b:?} are both bound to `{}`",
+                    key_name(bindings.key(a))
+                ));
+            }
+            if bindings.key(a) == EXIT_KEY {
+                return Err(format!(
+                    "{a:?} is bound to `{}`, which is kept for exiting",
+                    key_name(EXIT_KEY)
+                ));
+            }
+        }
+        Ok(bindings)
+    }
+
+    /// Bindings from the file at `path`
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let src = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read `{}`: {err}", path.display()))?;
+        Self::parse(&src).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    pub fn key(&self, action: Action) -> VirtualKeyCode {
+        self.keys[&action]
+    }
+
+    /// Name of the key of `action`, as written in the config file
+    pub fn key_name(&self, action: Action) -> &'static str {
+        key_name(self.key(action))
+    }
+
+    /// Whether the key of `action` is held down
+    pub fn held(&self, input: &WinitInputHelper, action: Action) -> bool {
+        input.key_held(self.key(action))
+    }
+
+    /// Whether the key of `action` was pressed since the last update
+    pub fn pressed(&self, input: &WinitInputHelper, action: Action) -> bool {
+        input.key_pressed(self.key(action))
+    }
+
+    /// Line per action saying what its key does, followed by the keys that cannot be rebound
+    pub fn help(&self) -> Vec<String> {
+        Action::ALL
+            .iter()
+            .map(|&action| format!("{}: {}", self.key_name(action), action.description()))
+            .chain([
+                "Shift/Ctrl: Move faster/slower".to_owned(),
+                format!("Ctrl-{}: Exit", key_name(EXIT_KEY)),
+            ])
+            .collect()
+    }
+}
+
+/// Conversions between the keys that can be bound and their names in the config file, which are
+/// those of their `VirtualKeyCode` variants
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+            match name {
+                $(stringify!($key) => Some(VirtualKeyCode::$key),)*
+                _ => None,
+            }
+        }
+
+        fn key_name(key: VirtualKeyCode) -> &'static str {
+            match key {
+                $(VirtualKeyCode::$key => stringify!($key),)*
+                _ => "?",
+            }
+        }
+    };
+}
+
+key_names! {
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down,
+    Back, Return, Space, Tab, Minus, Equals, LBracket, RBracket, Semicolon, Apostrophe, Comma,
+    Period, Slash, Backslash, Grave,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_bindings_are_the_defaults() {
+        let shipped = Bindings::parse(include_str!("../bindings.ron")).unwrap();
+        assert_eq!(shipped, Bindings::default());
+    }
+
+    #[test]
+    fn overrides_and_conflicts() {
+        let bindings = Bindings::parse(r#"{ MoveForward: "Up", ToggleHelp: "F1" }"#).unwrap();
+        assert_eq!(bindings.key(Action::MoveForward), VirtualKeyCode::Up);
+        assert_eq!(bindings.key(Action::MoveBackward), VirtualKeyCode::S);
+        assert!(Bindings::parse(r#"{ MoveForward: "S" }"#).is_err());
+        assert!(Bindings::parse(r#"{ MoveForward: "Nope" }"#).is_err());
+        assert!(Bindings::parse(r#"{ ToggleHelp: "C" }"#).is_err());
+    }
+
+    #[test]
+    fn key_names_round_trip() {
+        for action in Action::ALL {
+            let key = action.default_key();
+            assert_eq!(key_from_name(key_name(key)), Some(key));
+        }
+    }
+}
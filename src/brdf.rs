@@ -0,0 +1,167 @@
+use crate::math::Vec3;
+
+use serde::Deserialize;
+
+use std::f32::consts::PI;
+
+/// Metallic/roughness parameters of a physically based material. The base color is the color of
+/// the material.
+///
+/// Shading uses the Cook-Torrance microfacet model with a GGX distribution, the Smith geometry
+/// term and Schlick's Fresnel approximation.
+#[derive(Clone, Copy, Deserialize)]
+pub struct Pbr {
+    /// 0 for dielectrics, 1 for metals
+    pub metallic: f32,
+    /// Perceptual roughness, squared to get the width of the distribution
+    pub roughness: f32,
+}
+
+impl Pbr {
+    /// Smallest distribution width. Perfect mirrors would turn the distribution into a spike
+    /// that light sources can never hit.
+    const MIN_ALPHA: f32 = 1e-3;
+    /// Reflectance of dielectrics at normal incidence
+    const DIELECTRIC_F0: f32 = 0.04;
+
+    fn alpha(self) -> f32 {
+        (self.roughness * self.roughness).max(Self::MIN_ALPHA)
+    }
+
+    /// Schlick's approximation of the share of light reflected off a microfacet, where `cos` is
+    /// the cosine of the angle between the light and the microfacet normal
+    fn fresnel(self, base: [f32; 3], cos: f32) -> [f32; 3] {
+        let weight = (1.0 - cos).clamp(0.0, 1.0).powi(5);
+        base.map(|c| {
+            let f0 = Self::DIELECTRIC_F0 + (c - Self::DIELECTRIC_F0) * self.metallic;
+            f0 + (1.0 - f0) * weight
+        })
+    }
+
+    /// GGX normal distribution
+    fn distribution(alpha: f32, n_dot_h: f32) -> f32 {
+        let alpha_sq = alpha * alpha;
+        let denom = n_dot_h * n_dot_h * (alpha_sq - 1.0) + 1.0;
+        alpha_sq / (PI * denom * denom)
+    }
+
+    /// Smith masking-shadowing for both directions
+    fn geometry(alpha: f32, n_dot_v: f32, n_dot_l: f32) -> f32 {
+        let alpha_sq = alpha * alpha;
+        let g1 = |cos: f32| 2.0 * cos / (cos + (alpha_sq + (1.0 - alpha_sq) * cos * cos).sqrt());
+        g1(n_dot_v) * g1(n_dot_l)
+    }
+
+    /// Light reflected towards `view` from light arriving along `light`, including the cosine
+    /// of its incidence. Scaled by pi so that a white Lambertian surface lit head on reflects
+    /// everything, matching the plain shading of other materials.
+    pub fn eval(self, base: [f32; 3], normal: Vec3, view: Vec3, light: Vec3) -> [f32; 3] {
+        let n_dot_l = normal.dot(light);
+        let n_dot_v = normal.dot(view);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return [0.0; 3];
+        }
+        let half = (view + light).normalize();
+        let alpha = self.alpha();
+
+        let fresnel = self.fresnel(base, view.dot(half));
+        let specular = Self::distribution(alpha, normal.dot(half))
+            * Self::geometry(alpha, n_dot_v, n_dot_l)
+            / (4.0 * n_dot_v);
+        let diffuse = 1.0 - self.metallic;
+        [0, 1, 2].map(|i| {
+            let f = fresnel[i];
+            (f * specular * PI + (1.0 - f) * diffuse * base[i] * n_dot_l).max(0.0)
+        })
+    }
+
+    /// Importance sample the specular lobe: pick a direction for light reflected towards
+    /// `view` from two uniform random numbers, proportionally to the GGX distribution. Returns
+    /// the direction along with the share of the light from there that gets reflected,
+    /// already divided by the probability of picking it.
+    pub fn sample(
+        self,
+        base: [f32; 3],
+        normal: Vec3,
+        view: Vec3,
+        (r1, r2): (f32, f32),
+    ) -> Option<(Vec3, [f32; 3])> {
+        let alpha = self.alpha();
+        let n_dot_v = normal.dot(view);
+        if n_dot_v <= 0.0 {
+            return None;
+        }
+
+        // Microfacet normal, inverting the cumulative distribution of GGX
+        let cos_sq = (1.0 - r1) / (1.0 + (alpha * alpha - 1.0) * r1);
+        let (cos, sin) = (cos_sq.sqrt(), (1.0 - cos_sq).max(0.0).sqrt());
+        let (phi_sin, phi_cos) = (2.0 * PI * r2).sin_cos();
+        let (side, up) = normal.orthonormal_basis();
+        let half = normal * cos + side * (sin * phi_cos) + up * (sin * phi_sin);
+
+        let v_dot_h = view.dot(half);
+        let light = half * (2.0 * v_dot_h) - view;
+        let n_dot_l = normal.dot(light);
+        if n_dot_l <= 0.0 || v_dot_h <= 0.0 {
+            return None;
+        }
+
+        // The distribution cancels out with the probability of the sample
+        let weight = Self::geometry(alpha, n_dot_v, n_dot_l) * v_dot_h / (n_dot_v * cos);
+        Some((light, self.fresnel(base, v_dot_h).map(|f| f * weight)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Rng;
+
+    /// Rough dielectric seen from an angle
+    const PBR: Pbr = Pbr {
+        metallic: 0.0,
+        roughness: 0.5,
+    };
+
+    #[test]
+    fn lambertian_limit() {
+        // Without specular reflection, a dielectric reflects like a Lambertian surface
+        let normal = Vec3::K;
+        let light = Vec3::new(0.6, 0.0, 0.8);
+        let rough = Pbr {
+            metallic: 0.0,
+            roughness: 1.0,
+        };
+        let reflected = rough.eval([1.0; 3], normal, Vec3::new(0.0, 0.6, 0.8), light);
+        assert!((reflected[0] - 0.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn sample_matches_eval() {
+        // Averaging the weights of importance sampled directions must give the same result as
+        // integrating the specular part of `eval` over the hemisphere
+        let normal = Vec3::K;
+        let view = Vec3::new(0.0, 0.6, 0.8);
+        let black = [0.0; 3];
+        let mut rng = Rng::from_point(view);
+
+        let n = 200_000;
+        let mut sampled = 0.0;
+        let mut uniform = 0.0;
+        for _ in 0..n {
+            if let Some((_, weight)) =
+                PBR.sample(black, normal, view, (rng.next_f32(), rng.next_f32()))
+            {
+                sampled += weight[0];
+            }
+            // Uniform hemisphere sampling has a probability of 1 / (2 pi)
+            let z = rng.next_f32();
+            let radius = (1.0 - z * z).sqrt();
+            let (sin, cos) = (2.0 * PI * rng.next_f32()).sin_cos();
+            let light = Vec3::new(radius * cos, radius * sin, z);
+            uniform += PBR.eval(black, normal, view, light)[0] / PI * 2.0 * PI;
+        }
+        let (sampled, uniform) = (sampled / n as f32, uniform / n as f32);
+        assert!((sampled - uniform).abs() < 0.01, "{sampled} != {uniform}");
+    }
+}
@@ -0,0 +1,234 @@
+use crate::math::Vec3;
+
+/// Axis aligned bounding box
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Box containing nothing, the identity of `union`
+    pub const EMPTY: Self = Self {
+        min: Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        max: Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+    };
+
+    /// Box containing everything
+    pub const INFINITE: Self = Self {
+        min: Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        max: Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+    };
+
+    /// Smallest box containing all `points`
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points
+            .into_iter()
+            .fold(Self::EMPTY, |acc, p| acc.union(Self { min: p, max: p }))
+    }
+
+    #[must_use]
+    pub fn union(self, rhs: Self) -> Self {
+        Self {
+            min: self.min.min(rhs.min),
+            max: self.max.max(rhs.max),
+        }
+    }
+
+    pub fn centroid(self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    /// Whether the box is neither empty nor unbounded along any axis
+    pub fn is_finite(self) -> bool {
+        [self.min, self.max]
+            .iter()
+            .all(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+    }
+
+    /// Slab test, returning the `t` at which `base + ray * t` enters the box if that happens
+    /// before `t_max`
+    fn hit(self, base: Vec3, inv_ray: Vec3, t_max: f32) -> Option<f32> {
+        let t1 = Vec3::new(
+            (self.min.x - base.x) * inv_ray.x,
+            (self.min.y - base.y) * inv_ray.y,
+            (self.min.z - base.z) * inv_ray.z,
+        );
+        let t2 = Vec3::new(
+            (self.max.x - base.x) * inv_ray.x,
+            (self.max.y - base.y) * inv_ray.y,
+            (self.max.z - base.z) * inv_ray.z,
+        );
+        // `f32::min` and `f32::max` ignore the NaNs from rays parallel to a slab boundary
+        let t_enter = t1.x.min(t2.x).max(t1.y.min(t2.y)).max(t1.z.min(t2.z));
+        let t_exit = t1.x.max(t2.x).min(t1.y.max(t2.y)).min(t1.z.max(t2.z));
+        (t_enter <= t_exit && t_exit >= 0.0 && t_enter <= t_max).then_some(t_enter)
+    }
+}
+
+enum Node {
+    /// Range of `Bvh::indices` (Start, Count)
+    Leaf(u32, u32),
+    /// Index of the second child; the first one directly follows its parent
+    Inner(u32),
+}
+
+/// Bounding volume hierarchy over an indexed set of primitives
+#[derive(Default)]
+pub struct Bvh {
+    nodes: Vec<(Aabb, Node)>,
+    /// Primitive indices, ordered so that every leaf covers a contiguous range
+    indices: Vec<u32>,
+}
+
+impl Bvh {
+    /// Largest amount of primitives stored in a single leaf
+    const LEAF_SIZE: usize = 4;
+
+    /// Build a hierarchy over primitives with the given bounds
+    pub fn build(bounds: &[Aabb]) -> Self {
+        #[allow(clippy::cast_possible_truncation)]
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            indices: (0..bounds.len() as u32).collect(),
+        };
+        if !bounds.is_empty() {
+            bvh.build_node(bounds, 0, bounds.len());
+        }
+        bvh
+    }
+
+    /// Recursively build the node covering `indices[start..end]`, splitting at the median
+    /// along the axis in which the primitive centroids are most spread out
+    #[allow(clippy::cast_possible_truncation)]
+    fn build_node(&mut self, bounds: &[Aabb], start: usize, end: usize) {
+        let range = &mut self.indices[start..end];
+        let node_bounds = range
+            .iter()
+            .fold(Aabb::EMPTY, |acc, &i| acc.union(bounds[i as usize]));
+
+        if range.len() <= Self::LEAF_SIZE {
+            self.nodes
+                .push((node_bounds, Node::Leaf(start as u32, range.len() as u32)));
+            return;
+        }
+
+        let spread = Aabb::from_points(range.iter().map(|&i| bounds[i as usize].centroid()));
+        let extent = spread.max - spread.min;
+        let axis = |v: Vec3| {
+            if extent.x >= extent.y && extent.x >= extent.z {
+                v.x
+            } else if extent.y >= extent.z {
+                v.y
+            } else {
+                v.z
+            }
+        };
+        let mid = range.len() / 2;
+        range.select_nth_unstable_by(mid, |&a, &b| {
+            axis(bounds[a as usize].centroid()).total_cmp(&axis(bounds[b as usize].centroid()))
+        });
+
+        let node = self.nodes.len();
+        self.nodes.push((node_bounds, Node::Inner(0)));
+        self.build_node(bounds, start, start + mid);
+        self.nodes[node].1 = Node::Inner(self.nodes.len() as u32);
+        self.build_node(bounds, start + mid, end);
+    }
+
+    /// Bounds of all the primitives
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map_or(Aabb::EMPTY, |(bounds, _)| *bounds)
+    }
+
+    /// Find the closest primitive hit along `base + ray * t`.
+    ///
+    /// `intersect` is called with the index of every primitive whose bounds the ray passes
+    /// through, and returns the `t` of the hit along with any data to keep for it.
+    pub fn closest_hit<H>(
+        &self,
+        base: Vec3,
+        ray: Vec3,
+        mut intersect: impl FnMut(usize) -> Option<(f32, H)>,
+    ) -> Option<H> {
+        let inv_ray = Vec3::new(ray.x.recip(), ray.y.recip(), ray.z.recip());
+        let mut closest: Option<(f32, H)> = None;
+        let mut stack: Vec<usize> = Vec::with_capacity(32);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(node) = stack.pop() {
+            let (bounds, ref kind) = self.nodes[node];
+            let t_max = closest.as_ref().map_or(f32::INFINITY, |(t, _)| *t);
+            if bounds.hit(base, inv_ray, t_max).is_none() {
+                continue;
+            }
+            match *kind {
+                Node::Leaf(start, count) => {
+                    let range = start as usize..(start + count) as usize;
+                    for &i in &self.indices[range] {
+                        if let Some((t, data)) = intersect(i as usize) {
+                            if closest.as_ref().is_none_or(|(best, _)| t < *best) {
+                                closest = Some((t, data));
+                            }
+                        }
+                    }
+                }
+                Node::Inner(second) => {
+                    stack.push(second as usize);
+                    stack.push(node + 1);
+                }
+            }
+        }
+
+        closest.map(|(_, data)| data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Closest hit through the hierarchy must match testing every primitive
+    #[test]
+    fn matches_brute_force() {
+        let spheres: Vec<(Vec3, f32)> = (0..200)
+            .map(|i| {
+                let i = i as f32;
+                let pos = Vec3::new((i * 0.7).sin() * 3.0, (i * 1.3).cos() * 3.0 + 5.0, i * 0.03);
+                (pos, 0.1 + (i * 0.37).sin().abs() * 0.3)
+            })
+            .collect();
+        let bounds: Vec<Aabb> = spheres
+            .iter()
+            .map(|&(center, r)| Aabb {
+                min: center - Vec3::new(r, r, r),
+                max: center + Vec3::new(r, r, r),
+            })
+            .collect();
+        let bvh = Bvh::build(&bounds);
+
+        // Nearest `t` along the ray at which it passes within the sphere
+        let intersect = |base: Vec3, ray: Vec3, (center, r): (Vec3, f32)| {
+            let t = (center - base).dot(ray) / ray.sq_mag();
+            ((base + ray * t - center).sq_mag() < r * r && t > 0.0).then_some(t)
+        };
+
+        for k in 0..100 {
+            let k = k as f32;
+            let base = Vec3::new(k.sin(), -2.0, k.cos());
+            let ray = Vec3::new((k * 0.1).sin(), 1.0, (k * 0.2).cos() * 0.5);
+            let expected = (0..spheres.len())
+                .filter_map(|i| intersect(base, ray, spheres[i]).map(|t| (t, i)))
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, i)| i);
+            let found = bvh.closest_hit(base, ray, |i| {
+                intersect(base, ray, spheres[i]).map(|t| (t, i))
+            });
+            assert_eq!(found, expected);
+        }
+    }
+}
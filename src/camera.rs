@@ -1,8 +1,13 @@
 use crate::{
-    math::Vec3,
-    world::{Color, Object, Transform, World},
+    brdf::Pbr,
+    math::{Rng, Vec3},
+    mesh::TriangleMesh,
+    sdf::Sdf,
+    world::{AreaLight, Atmosphere, Color, Instance, Material, Object, Transform, World},
 };
 
+use std::cell::Cell;
+
 pub struct Camera {
     pub transform: Transform,
     pub px_per_unit: f32,
@@ -11,101 +16,546 @@ pub struct Camera {
 
 impl Camera {
     pub fn get_px(&self, world: &World, x: f32, y: f32) -> Color {
-        let ray = Vec3::new(
+        Color::from_rgb(self.get_px_hdr(world, x, y))
+    }
+
+    /// Color of a pixel without clamping channels brighter than white to `1.0`
+    pub fn get_px_hdr(&self, world: &World, x: f32, y: f32) -> [f32; 3] {
+        let ray = self.ray(x, y);
+        let base = self.transform.position;
+        let (color, t) = Self::raycast(base, ray, world, true, 0, None)
+            .unwrap_or_else(|| (world.background.radiance(ray), f32::INFINITY));
+        match world.atmosphere {
+            Some(ref atmosphere) => {
+                Self::apply_atmosphere(base, ray, t * ray.mag(), color, atmosphere, world)
+            }
+            None => color,
+        }
+    }
+
+    /// Raw value of `aov` for a pixel, see [`Aov`] for what each of them holds
+    pub fn get_aov(&self, world: &World, x: f32, y: f32, aov: Aov) -> [f32; 3] {
+        if aov == Aov::Beauty {
+            return self.get_px_hdr(world, x, y);
+        }
+
+        let ray = self.ray(x, y);
+        let base = self.transform.position;
+        count_ray(RayKind::Primary);
+        let Some(hit) = Self::closest_hit(base, ray, world, f32::INFINITY, None) else {
+            return match aov {
+                Aov::Depth => [f32::INFINITY; 3],
+                Aov::Albedo => world.background.radiance(ray),
+                Aov::Shadow => [1.0; 3],
+                Aov::Object => [-1.0; 3],
+                Aov::Beauty | Aov::Normal => [0.0; 3],
+            };
+        };
+        let coord = base + ray * hit.t;
+        match aov {
+            Aov::Beauty => unreachable!(),
+            Aov::Depth => [hit.t * ray.mag(); 3],
+            Aov::Normal => [hit.normal.x, hit.normal.y, hit.normal.z],
+            Aov::Albedo => hit.material.color_at(hit.uv, coord, hit.position).to_rgb(),
+            Aov::Shadow => {
+                let origin = Origin::of(&hit, coord, world);
+                let share = world
+                    .light
+                    .map_or([1.0; 3], |light| Self::transmittance(origin, light, world));
+                [share.iter().sum::<f32>() / 3.0; 3]
+            }
+            Aov::Object => [hit.object as f32; 3],
+        }
+    }
+
+    /// Color of a pixel as shown by `vis`, without clamping channels brighter than white
+    pub fn get_px_debug(&self, world: &World, x: f32, y: f32, vis: Visualization) -> [f32; 3] {
+        /// Width of wireframe lines, as the smallest barycentric coordinate of the hit
+        const WIRE_WIDTH: f32 = 0.02;
+        /// Intersection tests shown as the hottest color of the heatmap
+        const MAX_TESTS: u32 = 64;
+
+        match vis {
+            Visualization::Shaded => self.get_px_hdr(world, x, y),
+            Visualization::Normals => Aov::Normal.display(self.get_aov(world, x, y, Aov::Normal)),
+            Visualization::Depth => {
+                let [dist, ..] = self.get_aov(world, x, y, Aov::Depth);
+                if dist.is_finite() {
+                    heatmap(dist / (1.0 + dist))
+                } else {
+                    [0.0; 3]
+                }
+            }
+            Visualization::Wireframe => {
+                let hit = Self::closest_hit(
+                    self.transform.position,
+                    self.ray(x, y),
+                    world,
+                    f32::INFINITY,
+                    None,
+                );
+                let on_edge = hit.is_some_and(|hit| {
+                    let (u, v) = hit.bary;
+                    hit.on_triangle && u.min(v).min(1.0 - u - v) < WIRE_WIDTH
+                });
+                if on_edge {
+                    [0.0, 1.0, 0.0]
+                } else {
+                    self.get_px_hdr(world, x, y)
+                }
+            }
+            Visualization::Tests => {
+                INTERSECTION_TESTS.with(|tests| tests.set(0));
+                self.get_px_hdr(world, x, y);
+                let tests = INTERSECTION_TESTS.with(Cell::get);
+                heatmap(tests as f32 / MAX_TESTS as f32)
+            }
+            Visualization::Shadows => self.get_aov(world, x, y, Aov::Shadow),
+        }
+    }
+
+    /// Point of the world seen through the pixel at `(x, y)` from the center of the screen, if
+    /// there is one
+    pub fn pick(&self, world: &World, x: f32, y: f32) -> Option<Vec3> {
+        let ray = self.ray(x, y);
+        let base = self.transform.position;
+        Self::closest_hit(base, ray, world, f32::INFINITY, None).map(|hit| base + ray * hit.t)
+    }
+
+    /// Direction of the ray through the pixel at `(x, y)` from the center of the screen, with the
+    /// length of the focal length
+    fn ray(&self, x: f32, y: f32) -> Vec3 {
+        Vec3::new(
             x / self.px_per_unit,
             self.focal_length,
             -y / self.px_per_unit,
         )
-        .rotate(self.transform.rotation);
+        .rotate(self.transform.rotation)
+    }
+
+    /// Unclamped color of the closest hit along the ray, along with the `t` of the hit. `depth`
+    /// counts the reflections the ray went through, and `skip` is an object the ray cannot hit.
+    fn raycast(
+        base: Vec3,
+        ray: Vec3,
+        world: &World,
+        shadows: bool,
+        depth: u32,
+        skip: Option<usize>,
+    ) -> Option<([f32; 3], f32)> {
+        /// Reflections traced before giving up on a ray
+        const MAX_DEPTH: u32 = 3;
+
+        count_ray(if depth == 0 {
+            RayKind::Primary
+        } else {
+            RayKind::Secondary
+        });
+        let hit = Self::closest_hit(base, ray, world, f32::INFINITY, skip)?;
+        let coord = base + ray * hit.t;
+        let origin = Origin::of(&hit, coord, world);
+        let surface = Surface {
+            normal: hit
+                .material
+                .shading_normal(hit.uv, hit.normal, hit.tangents),
+            view: -ray.normalize(),
+            base: hit.material.color_at(hit.uv, coord, hit.position).to_rgb(),
+            pbr: hit.material.pbr,
+        };
+        let mut rng = Rng::from_point(coord);
 
-        Self::raycast(self.transform.position, ray, world, true)
-            .map_or(Color::BLACK, |hit| hit.color)
+        let mut rgb = [0.0; 3];
+        if let Some(light) = world.light {
+            let light_vec = (light - coord).normalize();
+            let share = if shadows {
+                Self::transmittance(origin, light, world)
+            } else {
+                [1.0; 3]
+            };
+            add_light(&mut rgb, share, surface.reflect(light_vec));
+        }
+        add_light(
+            &mut rgb,
+            [1.0; 3],
+            Self::area_lighting(origin, &surface, world, shadows, &mut rng),
+        );
+
+        if let Some(pbr) = surface.pbr {
+            if depth < MAX_DEPTH {
+                // Only the camera rays are worth splitting into several reflections
+                let samples = if depth == 0 { world.glossy_samples } else { 1 };
+                for _ in 0..samples {
+                    let Some((dir, share)) = pbr.sample(
+                        surface.base,
+                        surface.normal,
+                        surface.view,
+                        (rng.next_f32(), rng.next_f32()),
+                    ) else {
+                        continue;
+                    };
+                    let start = origin.start(dir, world);
+                    let incoming =
+                        Self::raycast(start, dir, world, shadows, depth + 1, origin.skip)
+                            .map_or_else(|| world.background.radiance(dir), |(color, _)| color);
+                    add_light(&mut rgb, incoming, share.map(|n| n / samples as f32));
+                }
+            }
+        }
+        if hit.material.is_transmissive() && depth < MAX_DEPTH {
+            // The surface only shows in the share of the light it does not let through
+            let tint = hit.material.transmission.to_rgb();
+            for i in 0..3 {
+                rgb[i] *= 1.0 - tint[i];
+            }
+            let dir = refract(surface.view, hit.normal, hit.material.ior);
+            let start = origin.start(dir, world);
+            let through = Self::raycast(start, dir, world, shadows, depth + 1, origin.skip)
+                .map_or_else(|| world.background.radiance(dir), |(color, _)| color);
+            add_light(&mut rgb, through, tint);
+        }
+        add_light(&mut rgb, hit.material.emitted().to_rgb(), [1.0; 3]);
+
+        Some((rgb, hit.t))
+    }
+
+    /// Light reflected at `origin` from the emissive objects of the world, estimated from random
+    /// samples taken on each of them
+    fn area_lighting(
+        origin: Origin,
+        surface: &Surface,
+        world: &World,
+        shadows: bool,
+        rng: &mut Rng,
+    ) -> [f32; 3] {
+        let coord = origin.point;
+        let samples = world.area_light_samples;
+        let mut rgb = [0.0; 3];
+        if samples == 0 {
+            return rgb;
+        }
+
+        for light in &world.area_lights {
+            let (emission, strength) = light.emission();
+            let emission = emission.to_rgb().map(|n| n * strength / samples as f32);
+            for _ in 0..samples {
+                let (point, light_normal, area) =
+                    light.sample(coord, (rng.next_f32(), rng.next_f32()));
+                let to_light = point - coord;
+                let dist_sq = to_light.sq_mag();
+                let dir = to_light / dist_sq.sqrt();
+                let cos_light = match light {
+                    AreaLight::Sphere(..) => -dir.dot(light_normal),
+                    // Triangles give off light on both sides
+                    AreaLight::Triangle(..) => dir.dot(light_normal).abs(),
+                };
+                if dir.dot(surface.normal) <= 0.0 || cos_light <= 0.0 {
+                    continue;
+                }
+                let visible = if shadows {
+                    Self::transmittance(origin, point, world)
+                } else {
+                    [1.0; 3]
+                };
+                // Solid angle of the sample, divided by pi like the point light
+                let solid_angle = cos_light * area / (std::f32::consts::PI * dist_sq);
+                let share = surface.reflect(dir);
+                for i in 0..3 {
+                    rgb[i] += emission[i] * visible[i] * share[i] * solid_angle;
+                }
+            }
+        }
+        rgb
+    }
+
+    /// Share of the light that makes it from `origin` to `to`, tinted by every transmissive
+    /// surface in between and stopped by any other
+    fn transmittance(origin: Origin, to: Vec3, world: &World) -> [f32; 3] {
+        let dir = (to - origin.point).normalize();
+        // Stop short of the surface `to` may be on
+        let margin = ray_offset(to, world.ray_bias);
+        let mut from = origin;
+
+        let mut share = [1.0; 3];
+        // Every surface let through starts another ray
+        loop {
+            count_ray(RayKind::Shadow);
+            let base = from.start(dir, world);
+            let dist = (to - base).mag() - margin;
+            let Some(hit) = Self::closest_hit(base, dir, world, dist, from.skip) else {
+                break;
+            };
+            let tint = hit.material.transmission.to_rgb();
+            for i in 0..3 {
+                share[i] *= tint[i];
+            }
+            if share.iter().all(|&n| n <= 0.0) {
+                break;
+            }
+            // Continue from the other side of the surface
+            from = Origin::of(&hit, base + dir * hit.t, world);
+        }
+        share
     }
 
-    fn raycast(base: Vec3, ray: Vec3, world: &World, shadows: bool) -> Option<RcHit> {
-        let mut hit = world
+    /// Color seen through the atmosphere, for a ray that reached `color` after traveling `dist`
+    /// units
+    fn apply_atmosphere(
+        base: Vec3,
+        ray: Vec3,
+        dist: f32,
+        color: [f32; 3],
+        atmosphere: &Atmosphere,
+        world: &World,
+    ) -> [f32; 3] {
+        match *atmosphere {
+            // Clear air, where rays that miss everything would otherwise get `0 * inf` of fog
+            Atmosphere::Fog(_, density) if density <= 0.0 => color,
+            Atmosphere::Fog(fog_color, density) => {
+                let transmittance = (-density * dist).exp();
+                let fog_color = fog_color.to_rgb();
+                [0, 1, 2].map(|i| fog_color[i] + (color[i] - fog_color[i]) * transmittance)
+            }
+            Atmosphere::Medium {
+                light_color,
+                scattering,
+                absorption,
+                steps,
+                max_dist,
+            } => {
+                // Single scattering, marched through the lit and shadowed parts of the medium
+                let extinction = scattering + absorption;
+                let march_dist = dist.min(max_dist);
+                let step = march_dist / steps as f32;
+                let dir = ray.normalize();
+                // Offsetting the samples differently for every ray trades banding for noise
+                let jitter =
+                    (dir.dot(Vec3::new(12.9898, 78.233, 37.719)).sin() * 43758.547).rem_euclid(1.0);
+                let mut transmittance = 1.0;
+                let mut scattered = [0.0; 3];
+                for i in 0..steps {
+                    let coord = base + dir * ((i as f32 + jitter) * step);
+                    if let Some(light) = world.light {
+                        let lit = Self::transmittance(Origin::in_space(coord), light, world);
+                        for (scattered, lit) in scattered.iter_mut().zip(lit) {
+                            *scattered += lit * transmittance * scattering * step;
+                        }
+                    }
+                    transmittance *= (-extinction * step).exp();
+                }
+                // Whatever lies past the end of the march is hidden by the rest of the medium
+                if dist > max_dist {
+                    transmittance *= (-extinction * (dist - max_dist)).exp();
+                }
+                let light_color = light_color.to_rgb();
+                [0, 1, 2].map(|i| color[i] * transmittance + light_color[i] * scattered[i])
+            }
+        }
+    }
+
+    /// Closest hit among the objects and instances of the world other than `skip`, if it is
+    /// before `max_t`
+    fn closest_hit(
+        base: Vec3,
+        ray: Vec3,
+        world: &World,
+        max_t: f32,
+        skip: Option<usize>,
+    ) -> Option<RcHit<'_>> {
+        let object_hit = world
             .objects
             .iter()
-            .filter_map(|obj| Self::calc_raycast(base, ray, obj))
-            .min_by(|a, b| a.t.total_cmp(&b.t))?;
-        let color = &mut hit.color;
-
-        let coord = base + ray * hit.t;
-        let light_vec = (world.light - coord).normalize();
-        if shadows && {
-            let max_t_sq = (world.light - coord).sq_mag();
-            world.objects.iter().any(|obj| {
-                // Check that the raycast hit is not the suface itself.
-                // `f32::EPSILON` is too small and creates visual artifacts.
-                Self::calc_raycast(coord, light_vec, obj)
-                    .is_some_and(|hit| hit.t > 1e-4 && hit.t * hit.t < max_t_sq)
+            .enumerate()
+            .filter(|&(i, _)| skip != Some(i))
+            .filter_map(|(i, obj)| {
+                Self::calc_raycast(base, ray, obj).map(|hit| RcHit { object: i, ..hit })
             })
-        } {
-            *color = Color::BLACK;
-        } else {
-            let illumination = light_vec.dot(hit.normal).max(0.0);
-            *color = *color * illumination;
-        }
+            .filter(|hit| hit.t < max_t)
+            .min_by(|a, b| a.t.total_cmp(&b.t));
+        let instance_hit = world.instance_bvh.closest_hit(base, ray, |i| {
+            let object = world.objects.len() + i;
+            if skip == Some(object) {
+                return None;
+            }
+            Self::calc_instance_raycast(base, ray, &world.placed[i])
+                .filter(|hit| hit.t < max_t)
+                .map(|hit| (hit.t, RcHit { object, ..hit }))
+        });
+        [object_hit, instance_hit]
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| a.t.total_cmp(&b.t))
+    }
 
+    fn calc_instance_raycast(base: Vec3, ray: Vec3, instance: &Instance) -> Option<RcHit<'_>> {
+        let transform = &instance.transform;
+        let mut hit = Self::calc_raycast(
+            transform.point_to_local(base),
+            transform.dir_to_local(ray),
+            &instance.object,
+        )?;
+        hit.normal = transform.normal_to_world(hit.normal);
+        hit.geometric_normal = transform.normal_to_world(hit.geometric_normal);
+        // Only the directions of the tangents matter, so they map like normals
+        hit.tangents = (
+            transform.normal_to_world(hit.tangents.0),
+            transform.normal_to_world(hit.tangents.1),
+        );
+        if let Some(material) = &instance.material_override {
+            hit.material = material;
+        }
         Some(hit)
     }
 
-    fn calc_raycast(base: Vec3, ray: Vec3, obj: &Object) -> Option<RcHit> {
-        match *obj {
-            Object::Sphere(center, r, color) => {
-                Self::calc_sphere_raycast(base, ray, (center, r, color))
+    fn calc_raycast(base: Vec3, ray: Vec3, obj: &Object) -> Option<RcHit<'_>> {
+        let mut hit = match *obj {
+            Object::Sphere(center, r, ref material) => {
+                Self::calc_sphere_raycast(base, ray, (center, r, material))
+            }
+            Object::Triangle(p1, p2, p3, ref material) => {
+                Self::calc_tri_raycast(base, ray, (p1, p2, p3, material))
+            }
+            Object::SmoothTriangle(p1, p2, p3, normals, ref material) => {
+                Self::calc_tri_raycast(base, ray, (p1, p2, p3, material)).map(|mut hit| {
+                    hit.normal = interpolate(normals, hit.bary).normalize();
+                    hit
+                })
             }
-            Object::Triangle(p1, p2, p3, color) => {
-                Self::calc_tri_raycast(base, ray, (p1, p2, p3, color))
+            Object::Sdf(ref sdf, ref material) => {
+                Self::calc_sdf_raycast(base, ray, (sdf, material))
             }
+            Object::Mesh(ref mesh) => Self::calc_mesh_raycast(base, ray, mesh),
+        }?;
+        hit.position = base + ray * hit.t;
+        Some(hit)
+    }
+
+    fn calc_mesh_raycast(base: Vec3, ray: Vec3, mesh: &TriangleMesh) -> Option<RcHit<'_>> {
+        let material = &mesh.material;
+        let (index, mut hit) = mesh.bvh.closest_hit(base, ray, |i| {
+            let [p1, p2, p3] = mesh.triangle(i);
+            Self::calc_tri_raycast(base, ray, (p1, p2, p3, material)).map(|hit| (hit.t, (i, hit)))
+        })?;
+
+        let vertices = mesh.indices[index].map(|i| i as usize);
+        if let Some(normals) = &mesh.normals {
+            hit.normal = interpolate(vertices.map(|i| normals[i]), hit.bary).normalize();
         }
+        if let Some(uvs) = &mesh.uvs {
+            // Interpolated as vectors in the xy plane
+            let uv = interpolate(
+                vertices.map(|i| Vec3::new(uvs[i].0, uvs[i].1, 0.0)),
+                hit.bary,
+            );
+            hit.uv = (uv.x, uv.y);
+
+            // Solve `edge = tangent * delta_u + bitangent * delta_v` for both edges
+            let [p1, p2, p3] = mesh.triangle(index);
+            let (e1, e2) = (p2 - p1, p3 - p1);
+            let [uv1, uv2, uv3] = vertices.map(|i| uvs[i]);
+            let (d1, d2) = (
+                (uv2.0 - uv1.0, uv2.1 - uv1.1),
+                (uv3.0 - uv1.0, uv3.1 - uv1.1),
+            );
+            let det = d1.0 * d2.1 - d2.0 * d1.1;
+            hit.tangents = if det.abs() > f32::EPSILON {
+                ((e1 * d2.1 - e2 * d1.1) / det, (e2 * d1.0 - e1 * d2.0) / det)
+            } else {
+                (Vec3::default(), Vec3::default())
+            };
+        }
+
+        Some(hit)
     }
 
     fn calc_tri_raycast(
         base: Vec3,
         ray: Vec3,
-        (p1, p2, p3, color): (Vec3, Vec3, Vec3, Color),
-    ) -> Option<RcHit> {
-        // Check if within plane
-        let v1 = p2 - p1;
-        let v2 = p3 - p1;
-        let dist = base - p1;
-        let cross = v1.cross(v2);
-        let t = -cross.dot(dist) / cross.dot(ray);
-
-        if !t.is_finite() || t.is_sign_negative() {
+        (p1, p2, p3, material): (Vec3, Vec3, Vec3, &Material),
+    ) -> Option<RcHit<'_>> {
+        count_test();
+
+        // Watertight test of Woop et al.: move the ray to the origin and shear the triangle so that
+        // the ray points along the largest axis, leaving a 2D test of which side of each edge the
+        // origin is on. A shared edge is sheared the same way for both of its triangles, so they
+        // see exactly opposite signs and the ray hits exactly one of them, with no epsilon involved.
+        let xyz = |v: Vec3| [v.x, v.y, v.z];
+        let (dir, abs) = (xyz(ray), xyz(ray.abs()));
+        let kz = if abs[0] >= abs[1] && abs[0] >= abs[2] {
+            0
+        } else if abs[1] >= abs[2] {
+            1
+        } else {
+            2
+        };
+        let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        if dir[kz] < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+        let (sx, sy, sz) = (dir[kx] / dir[kz], dir[ky] / dir[kz], dir[kz].recip());
+        let [s1, s2, s3] = [p1, p2, p3].map(|p| {
+            let p = xyz(p - base);
+            (p[kx] - sx * p[kz], p[ky] - sy * p[kz], sz * p[kz])
+        });
+
+        // Twice the signed area of the origin and the edge from `p` to `q`, worked out again in
+        // f64 when it rounds to zero in f32
+        #[allow(clippy::cast_possible_truncation)]
+        let edge = |p: (f32, f32, f32), q: (f32, f32, f32)| {
+            let area = p.0 * q.1 - p.1 * q.0;
+            if area == 0.0 {
+                (f64::from(p.0) * f64::from(q.1) - f64::from(p.1) * f64::from(q.0)) as f32
+            } else {
+                area
+            }
+        };
+        let edges = [(s2, s3), (s3, s1), (s1, s2)];
+        let weights = edges.map(|(p, q)| edge(p, q));
+        let det: f32 = weights.iter().sum();
+
+        // A ray right on an edge belongs to the triangle on one side of it only, picked by which
+        // way the edge runs with the triangle wound counterclockwise, like the top-left rule of
+        // rasterizers
+        let owns = |(p, q): ((f32, f32, f32), (f32, f32, f32))| {
+            let (dx, dy) = if det > 0.0 {
+                (q.0 - p.0, q.1 - p.1)
+            } else {
+                (p.0 - q.0, p.1 - q.1)
+            };
+            dy > 0.0 || (dy == 0.0 && dx < 0.0)
+        };
+        let inside = weights
+            .iter()
+            .zip(edges)
+            .all(|(&w, edge)| w * det.signum() > 0.0 || (w == 0.0 && owns(edge)));
+        // All weights are zero for rays within the plane of the triangle
+        if det == 0.0 || !inside {
             return None;
         }
 
-        // Check if within tetrahedron
-        let [(x1, x2, x3), (y1, y2, y3), (z1, z2, z3)] =
-            [p1, p2, p3].map(|p| p - base).map(|v| (v.x, v.y, v.z));
-
-        let y2z3_z2y3 = y2 * z3 - z2 * y3;
-        let z2x3_x2z3 = z2 * x3 - x2 * z3;
-        let x2y3_y2x3 = x2 * y3 - y2 * x3;
-        let det_neg = (x1 * y2z3_z2y3 + y1 * z2x3_x2z3 + z1 * x2y3_y2x3).is_sign_negative();
-        if ![
-            ray.x * y2z3_z2y3 + ray.y * (z1 * y3 - y1 * z3) + ray.z * (y1 * z2 - z1 * y2),
-            ray.x * z2x3_x2z3 + ray.y * (x1 * z3 - z1 * x3) + ray.z * (z1 * x2 - x1 * z2),
-            ray.x * x2y3_y2x3 + ray.y * (y1 * x3 - x1 * y3) + ray.z * (x1 * y2 - y1 * x2),
-        ]
-        .iter()
-        .all(|n| n.is_sign_positive() ^ det_neg)
-        {
+        let t = (weights[0] * s1.2 + weights[1] * s2.2 + weights[2] * s3.2) / det;
+        if t < 0.0 {
             return None;
         }
+        let (u, v) = (weights[1] / det, weights[2] / det);
 
-        let normal = cross.normalize();
+        let (v1, v2) = (p2 - p1, p3 - p1);
+        let normal = v1.cross(v2).normalize();
 
-        Some(RcHit::new(color, t, normal))
+        // Barycentric texture coordinates increase along the edges from the first point
+        Some(
+            RcHit::new(material, t, normal)
+                .with_bary(u, v)
+                .with_tangents(v1, v2),
+        )
     }
 
     fn calc_sphere_raycast(
         base: Vec3,
         ray: Vec3,
-        (center, r, color): (Vec3, f32, Color),
-    ) -> Option<RcHit> {
+        (center, r, material): (Vec3, f32, &Material),
+    ) -> Option<RcHit<'_>> {
+        count_test();
+
         let dist = center - base;
         let ray_sqmag = ray.sq_mag();
         // SAFETY: `ray_sqmag` will always be positive; we let LLVM know so this can be optimized.
@@ -135,18 +585,651 @@ impl Camera {
         let coord = base + ray * t;
         let normal = (coord - center).normalize();
 
-        Some(RcHit::new(color, t, normal))
+        // Spherical mapping, with the seam on -x and the poles on the z axis
+        let uv = (
+            0.5 + normal.y.atan2(normal.x) * std::f32::consts::FRAC_1_PI / 2.0,
+            normal.z.clamp(-1.0, 1.0).acos() * std::f32::consts::FRAC_1_PI,
+        );
+
+        // Derivatives of the mapping, degenerate at the poles
+        let tangents = (
+            Vec3::new(-normal.y, normal.x, 0.0),
+            Vec3::new(
+                normal.z * normal.x,
+                normal.z * normal.y,
+                -(normal.x * normal.x + normal.y * normal.y),
+            ),
+        );
+
+        Some(
+            RcHit::new(material, t, normal)
+                .with_uv(uv)
+                .with_tangents(tangents.0, tangents.1),
+        )
     }
+
+    fn calc_sdf_raycast<'a>(
+        base: Vec3,
+        ray: Vec3,
+        (sdf, material): (&Sdf, &'a Material),
+    ) -> Option<RcHit<'a>> {
+        count_test();
+
+        /// Distance to the surface at which the march counts as a hit.
+        const HIT_DIST: f32 = 1e-4;
+        /// Distance the march starts at, so that rays leaving a surface do not hit it immediately.
+        const START_DIST: f32 = 2.0 * HIT_DIST;
+        const MAX_DIST: f32 = 100.0;
+        const MAX_STEPS: u32 = 256;
+
+        // March in world units along a unit direction, then convert back to the `t` of `ray`.
+        let ray_mag = ray.mag();
+        let dir = ray / ray_mag;
+
+        let mut dist = START_DIST;
+        for _ in 0..MAX_STEPS {
+            let coord = base + dir * dist;
+            let step = sdf.distance(coord);
+            if step < HIT_DIST {
+                return Some(RcHit::new(material, dist / ray_mag, sdf.normal(coord)));
+            }
+            dist += step;
+            if dist > MAX_DIST {
+                break;
+            }
+        }
+
+        None
+    }
+}
+
+/// Buffer rendered for compositing and debugging, as an alternative or alongside the shaded
+/// image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aov {
+    /// Shaded image
+    Beauty,
+    /// Distance from the camera to the hit, infinite for rays that hit nothing
+    Depth,
+    /// World space geometric normal of the hit, zero for rays that hit nothing
+    Normal,
+    /// Unshaded color of the material, or the background for rays that hit nothing
+    Albedo,
+    /// Share of the point light reaching the hit, from `0.0` in full shadow to `1.0`
+    Shadow,
+    /// Index of the hit object, `-1.0` for rays that hit nothing
+    Object,
 }
 
-struct RcHit {
-    color: Color,
+impl std::str::FromStr for Aov {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|aov| aov.name() == name)
+            .ok_or_else(|| format!("unknown AOV `{name}`"))
+    }
+}
+
+impl Aov {
+    pub const ALL: [Self; 6] = [
+        Self::Beauty,
+        Self::Depth,
+        Self::Normal,
+        Self::Albedo,
+        Self::Shadow,
+        Self::Object,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Beauty => "beauty",
+            Self::Depth => "depth",
+            Self::Normal => "normal",
+            Self::Albedo => "albedo",
+            Self::Shadow => "shadow",
+            Self::Object => "object",
+        }
+    }
+
+    /// Buffer after this one, wrapping around to the beauty
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    /// Whether the values are gamma encoded colors rather than data
+    pub const fn is_color(self) -> bool {
+        matches!(self, Self::Beauty | Self::Albedo)
+    }
+
+    /// Names of the channels stored for the buffer, taking the leading values of each pixel
+    pub const fn channels(self) -> &'static [&'static str] {
+        match self {
+            Self::Depth => &["Z"],
+            Self::Shadow | Self::Object => &["Y"],
+            _ => &["R", "G", "B"],
+        }
+    }
+
+    /// Color in `0.0..=1.0` showing a raw value of the buffer
+    pub fn display(self, value: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::Beauty | Self::Albedo | Self::Shadow => value,
+            // Bright up close, fading to black in the distance
+            Self::Depth => value.map(|dist| 1.0 / (1.0 + dist)),
+            Self::Normal => value.map(|n| 0.5 + 0.5 * n),
+            Self::Object if value[0] < 0.0 => [0.0; 3],
+            Self::Object => {
+                // Hues spread by the golden ratio so that neighbouring indices stand apart
+                let hue = (value[0] * 0.618_034).fract();
+                [0.0, 1.0, 2.0]
+                    .map(|offset| 0.5 + 0.5 * (std::f32::consts::TAU * (hue + offset / 3.0)).cos())
+            }
+        }
+    }
+}
+
+/// Diagnostic view of the scene, shown in the viewer in place of the shaded image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visualization {
+    Shaded,
+    /// World space normals as RGB
+    Normals,
+    /// Distance to the camera as a heatmap, from blue up close to red in the distance
+    Depth,
+    /// Shaded image with the edges of triangles drawn over it
+    Wireframe,
+    /// Ray-primitive intersection tests made while shading each pixel, as a heatmap
+    Tests,
+    /// Share of the point light reaching the surface, without any shading
+    Shadows,
+}
+
+impl Visualization {
+    pub const ALL: [Self; 6] = [
+        Self::Shaded,
+        Self::Normals,
+        Self::Depth,
+        Self::Wireframe,
+        Self::Tests,
+        Self::Shadows,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Shaded => "shaded",
+            Self::Normals => "normals",
+            Self::Depth => "depth",
+            Self::Wireframe => "wireframe",
+            Self::Tests => "intersection tests",
+            Self::Shadows => "shadows",
+        }
+    }
+
+    /// View after this one, wrapping around to the shaded image
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+thread_local! {
+    /// Ray-primitive intersection tests made on this thread, for [`Visualization::Tests`]
+    static INTERSECTION_TESTS: Cell<u32> = const { Cell::new(0) };
+}
+
+fn count_test() {
+    INTERSECTION_TESTS.with(|tests| tests.set(tests.get() + 1));
+}
+
+/// Rays traced, by what they were traced for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RayCounts {
+    /// Rays from the camera
+    pub primary: u64,
+    /// Rays towards lights, including those sampling a medium
+    pub shadow: u64,
+    /// Reflected and refracted rays
+    pub secondary: u64,
+}
+
+impl std::ops::AddAssign for RayCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.primary += other.primary;
+        self.shadow += other.shadow;
+        self.secondary += other.secondary;
+    }
+}
+
+impl RayCounts {
+    pub const fn total(self) -> u64 {
+        self.primary + self.shadow + self.secondary
+    }
+
+    /// Take the rays counted on this thread since the last time they were taken
+    pub fn take() -> Self {
+        RAY_COUNTS.with(Cell::take)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum RayKind {
+    Primary,
+    Shadow,
+    Secondary,
+}
+
+thread_local! {
+    /// Rays traced on this thread, for [`RayCounts::take`]
+    static RAY_COUNTS: Cell<RayCounts> = const {
+        Cell::new(RayCounts {
+            primary: 0,
+            shadow: 0,
+            secondary: 0,
+        })
+    };
+}
+
+fn count_ray(kind: RayKind) {
+    RAY_COUNTS.with(|counts| {
+        let mut new = counts.get();
+        match kind {
+            RayKind::Primary => new.primary += 1,
+            RayKind::Shadow => new.shadow += 1,
+            RayKind::Secondary => new.secondary += 1,
+        }
+        counts.set(new);
+    });
+}
+
+/// Color going from blue at `0.0` through green to red at `1.0` and above
+fn heatmap(t: f32) -> [f32; 3] {
+    let t = t.clamp(0.0, 1.0);
+    [
+        (2.0 * t - 1.0).max(0.0),
+        1.0 - (2.0 * t - 1.0).abs(),
+        (1.0 - 2.0 * t).max(0.0),
+    ]
+}
+
+/// Point on a surface that secondary rays leave from
+#[derive(Clone, Copy)]
+struct Origin {
+    point: Vec3,
+    /// Geometric normal of the surface
+    normal: Vec3,
+    /// Object that rays leaving the point cannot hit again, for flat ones
+    skip: Option<usize>,
+}
+
+impl Origin {
+    /// Point away from any surface, such as inside a medium
+    fn in_space(point: Vec3) -> Self {
+        Self {
+            point,
+            normal: Vec3::default(),
+            skip: None,
+        }
+    }
+
+    fn of(hit: &RcHit, point: Vec3, world: &World) -> Self {
+        let object = world.objects.get(hit.object).or_else(|| {
+            world
+                .placed
+                .get(hit.object - world.objects.len())
+                .map(|instance| &*instance.object)
+        });
+        let flat = matches!(
+            object,
+            Some(Object::Triangle(..) | Object::SmoothTriangle(..))
+        );
+        Self {
+            point,
+            normal: hit.geometric_normal,
+            skip: flat.then_some(hit.object),
+        }
+    }
+
+    /// Start of a ray leaving along `dir`, moved off the surface to the side `dir` points to
+    fn start(&self, dir: Vec3, world: &World) -> Vec3 {
+        let offset = ray_offset(self.point, world.ray_bias);
+        self.point + self.normal * (offset * dir.dot(self.normal).signum())
+    }
+}
+
+/// Distance rays leaving a surface at `point` start off it, so that rounding errors in the hit do
+/// not make them hit the surface again. Grows with the magnitude of the coordinates, like the
+/// precision of `f32` does, on top of the scene's `bias`.
+fn ray_offset(point: Vec3, bias: f32) -> f32 {
+    /// Relative error allowed in the coordinates of computed hits
+    const REL_EPS: f32 = 32.0 * f32::EPSILON;
+    /// Offset near the origin, where the relative error alone would vanish
+    const ABS_EPS: f32 = 1e-5;
+    let magnitude = point.x.abs().max(point.y.abs()).max(point.z.abs());
+    bias + ABS_EPS + REL_EPS * magnitude
+}
+
+/// Shading parameters of a point seen by a ray
+struct Surface {
+    normal: Vec3,
+    /// Unit vector towards where the ray came from
+    view: Vec3,
+    /// Color of the material at the point
+    base: [f32; 3],
+    pbr: Option<Pbr>,
+}
+
+impl Surface {
+    /// Share of the light arriving along `light` that is reflected towards the viewer,
+    /// including the cosine of its incidence
+    fn reflect(&self, light: Vec3) -> [f32; 3] {
+        if let Some(pbr) = self.pbr {
+            pbr.eval(self.base, self.normal, self.view, light)
+        } else {
+            let illumination = light.dot(self.normal).max(0.0);
+            self.base.map(|n| n * illumination)
+        }
+    }
+}
+
+/// Add the light arriving in each channel, times the share of it that is reflected
+fn add_light(rgb: &mut [f32; 3], light: [f32; 3], share: [f32; 3]) {
+    for i in 0..3 {
+        rgb[i] += light[i] * share[i];
+    }
+}
+
+/// Direction of a ray seen along `view` through a surface with the given `normal` and index of
+/// refraction. Rays leaving the surface from the side the normal points to use the inverse
+/// index. Totally reflected rays are reflected.
+fn refract(view: Vec3, normal: Vec3, ior: f32) -> Vec3 {
+    let cos_in = view.dot(normal);
+    let (normal, cos_in, eta) = if cos_in >= 0.0 {
+        (normal, cos_in, ior.recip())
+    } else {
+        (-normal, -cos_in, ior)
+    };
+    let cos_out_sq = 1.0 - eta * eta * (1.0 - cos_in * cos_in);
+    if cos_out_sq < 0.0 {
+        return normal * (2.0 * cos_in) - view;
+    }
+    -view * eta + normal * (eta * cos_in - cos_out_sq.sqrt())
+}
+
+/// Interpolate values given at the points of a triangle, using barycentric coordinates as
+/// stored in [`RcHit::bary`]
+fn interpolate([p1, p2, p3]: [Vec3; 3], (u, v): (f32, f32)) -> Vec3 {
+    p1 * (1.0 - u - v) + p2 * u + p3 * v
+}
+
+struct RcHit<'a> {
+    material: &'a Material,
     t: f32,
     normal: Vec3,
+    /// Normal of the surface itself, which `normal` differs from on smooth triangles
+    geometric_normal: Vec3,
+    /// Barycentric coordinates of the hit on a triangle, as the weights of its second and third
+    /// points. Always `(0.0, 0.0)` for other objects.
+    bary: (f32, f32),
+    /// Whether the hit is on a triangle, so that `bary` is meaningful
+    on_triangle: bool,
+    /// Texture coordinates of the hit
+    uv: (f32, f32),
+    /// Position of the hit in the local space of the object
+    position: Vec3,
+    /// Directions in which the texture coordinates `u` and `v` increase along the surface. Zero
+    /// for objects without texture coordinates.
+    tangents: (Vec3, Vec3),
+    /// Index of the hit object in `World::objects`, followed by `World::placed`. Set by
+    /// `Camera::closest_hit`.
+    object: usize,
+}
+
+impl<'a> RcHit<'a> {
+    const fn new(material: &'a Material, t: f32, normal: Vec3) -> Self {
+        Self {
+            material,
+            t,
+            normal,
+            geometric_normal: normal,
+            bary: (0.0, 0.0),
+            on_triangle: false,
+            uv: (0.0, 0.0),
+            position: Vec3::default(),
+            tangents: (Vec3::default(), Vec3::default()),
+            object: 0,
+        }
+    }
+
+    /// Set the barycentric coordinates, which are also the default texture coordinates of
+    /// triangles
+    const fn with_bary(self, u: f32, v: f32) -> Self {
+        Self {
+            bary: (u, v),
+            on_triangle: true,
+            uv: (u, v),
+            ..self
+        }
+    }
+
+    const fn with_uv(self, uv: (f32, f32)) -> Self {
+        Self { uv, ..self }
+    }
+
+    const fn with_tangents(self, tangent: Vec3, bitangent: Vec3) -> Self {
+        Self {
+            tangents: (tangent, bitangent),
+            ..self
+        }
+    }
 }
 
-impl RcHit {
-    const fn new(color: Color, t: f32, normal: Vec3) -> Self {
-        Self { color, t, normal }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static MATERIAL: Material = Material::plain(Color::BLACK);
+
+    const TRI: (Vec3, Vec3, Vec3, &Material) = (
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 1.0),
+        &MATERIAL,
+    );
+
+    fn cast_at(target: Vec3) -> Option<RcHit<'static>> {
+        let base = Vec3::new(0.2, -1.0, 0.3);
+        Camera::calc_tri_raycast(base, target - base, TRI)
+    }
+
+    #[test]
+    fn refraction() {
+        let normal = Vec3::K;
+        let view = Vec3::new(0.6, 0.0, 0.8);
+        // Without bending, the ray carries on straight
+        assert!((refract(view, normal, 1.0) + view).mag() < 1e-6);
+        // Snell's law, going in and back out
+        let inside = refract(view, normal, 1.5);
+        assert!((inside.mag() - 1.0).abs() < 1e-5);
+        assert!((inside.x.abs() * 1.5 - 0.6).abs() < 1e-5);
+        let outside = refract(inside, normal, 1.5);
+        assert!((outside - view).mag() < 1e-5);
+        // Past the critical angle, rays leaving the denser side are reflected
+        let grazing = Vec3::new(0.8, 0.0, -0.6);
+        let reflected = refract(grazing, normal, 1.5);
+        assert!((reflected - Vec3::new(-0.8, 0.0, -0.6)).mag() < 1e-5);
+    }
+
+    #[test]
+    fn aov_cycle() {
+        for aov in Aov::ALL {
+            assert_eq!(aov.name().parse(), Ok(aov));
+        }
+        assert_eq!(Aov::Beauty.next(), Aov::Depth);
+        assert_eq!(Aov::Object.next(), Aov::Beauty);
+        assert!("mask".parse::<Aov>().is_err());
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn shadow_rays_far_from_origin() {
+        // Where `f32` coordinates are coarser than the offset that used to be fixed
+        let center = Vec3::new(1e5, -2e5, 1e5);
+        let world = World {
+            objects: vec![Object::Sphere(center, 10.0, Material::plain(Color::BLACK))],
+            ..World::default()
+        };
+        let base = center + Vec3::new(0.3, -0.2, 20.0);
+        let ray = Vec3::new(0.0, 0.0, -1.0);
+        let hit = Camera::closest_hit(base, ray, &world, f32::INFINITY, None).unwrap();
+        let origin = Origin::of(&hit, base + ray * hit.t, &world);
+        assert_eq!(origin.skip, None);
+        let light = center + Vec3::new(1.0, 2.0, 30.0);
+        assert_eq!(Camera::transmittance(origin, light, &world), [1.0; 3]);
+        // The far side of the sphere is still in its shadow
+        let below = center - Vec3::new(0.0, 0.0, 30.0);
+        assert_eq!(Camera::transmittance(origin, below, &world), [0.0; 3]);
+    }
+
+    #[test]
+    fn fog_density() {
+        let world = World::default();
+        let fog = |density| Atmosphere::Fog(Color([0, 0, 255]), density);
+        let sky = [0.5, 0.7, 1.0];
+        let seen = |density, dist| {
+            Camera::apply_atmosphere(Vec3::default(), Vec3::J, dist, sky, &fog(density), &world)
+        };
+        assert!(seen(0.0, f32::INFINITY)
+            .iter()
+            .zip(sky)
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+        // Far enough into thick fog, only the fog is seen
+        assert!(seen(0.5, f32::INFINITY)
+            .iter()
+            .zip([0.0, 0.0, 1.0])
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+    }
+
+    #[test]
+    fn heatmap_ends() {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-6);
+        assert!(close(heatmap(0.0), [0.0, 0.0, 1.0]));
+        assert!(close(heatmap(0.5), [0.0, 1.0, 0.0]));
+        assert!(close(heatmap(1.0), [1.0, 0.0, 0.0]));
+        assert!(close(heatmap(7.0), heatmap(1.0)));
+    }
+
+    #[test]
+    fn tri_interior() {
+        let hit = cast_at(Vec3::new(0.25, 1.0, 0.5)).expect("ray should hit");
+        assert!((hit.t - 1.0).abs() < 1e-5);
+        assert!((hit.bary.0 - 0.25).abs() < 1e-5);
+        assert!((hit.bary.1 - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn tri_miss() {
+        assert!(cast_at(Vec3::new(0.6, 1.0, 0.6)).is_none());
+        assert!(cast_at(Vec3::new(-0.1, 1.0, 0.5)).is_none());
+    }
+
+    /// Rays just inside the corners, as the corners themselves belong to the triangle only
+    /// where it owns both edges that meet there.
+    #[test]
+    fn tri_vertices() {
+        let center = (TRI.0 + TRI.1 + TRI.2) / 3.0;
+        for (vertex, bary) in [
+            (TRI.0, (0.0, 0.0)),
+            (TRI.1, (1.0, 0.0)),
+            (TRI.2, (0.0, 1.0)),
+        ] {
+            let target = vertex + (center - vertex) * 1e-4;
+            let hit = cast_at(target).expect("ray at vertex should hit");
+            assert!((hit.bary.0 - bary.0).abs() < 1e-3);
+            assert!((hit.bary.1 - bary.1).abs() < 1e-3);
+        }
+    }
+
+    /// Rays at an edge of the triangle, far from the origin too, hit either it or its mirror image
+    /// across the edge, whichever way the mirror image is wound, but never both.
+    #[test]
+    fn tri_edges() {
+        let (p1, p2, p3, material) = TRI;
+        for offset in [Vec3::default(), Vec3::new(3e4, -2e4, 5e4)] {
+            let tri = (p1 + offset, p2 + offset, p3 + offset, material);
+            for (e1, e2, opposite) in [(p1, p2, p3), (p2, p3, p1), (p3, p1, p2)] {
+                let (e1, e2, opposite) = (e1 + offset, e2 + offset, opposite + offset);
+                let mirror = e1 + e2 - opposite;
+                let base = Vec3::new(0.2, -1.0, 0.3) + offset;
+                for step in 1..8 {
+                    let ray = e1 + (e2 - e1) * (step as f32 / 8.0) - base;
+                    for neighbour in [(e1, e2, mirror, material), (e2, e1, mirror, material)] {
+                        let hits = [tri, neighbour]
+                            .iter()
+                            .filter(|&&tri| Camera::calc_tri_raycast(base, ray, tri).is_some())
+                            .count();
+                        assert_eq!(hits, 1, "{hits} hits at step {step} of {e1:?} to {e2:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tri_edge_on() {
+        // Ray within the plane of the triangle
+        let hit = Camera::calc_tri_raycast(Vec3::new(-1.0, 1.0, 0.2), Vec3::I, TRI);
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn tri_behind() {
+        let hit = Camera::calc_tri_raycast(Vec3::new(0.2, 2.0, 0.2), Vec3::J, TRI);
+        assert!(hit.is_none());
+    }
+
+    /// Rays aimed along every edge shared by two triangles of the cube scene must hit exactly one
+    /// of them, away from the corners where other triangles meet.
+    #[test]
+    fn cube_no_cracks() {
+        let world = World::load(include_str!("../scenes/cube.ron")).unwrap();
+        let tris: Vec<_> = world
+            .objects
+            .iter()
+            .filter_map(|obj| match *obj {
+                Object::Triangle(p1, p2, p3, ref material) => Some((p1, p2, p3, material)),
+                _ => None,
+            })
+            .collect();
+        let center = tris
+            .iter()
+            .fold(Vec3::default(), |acc, tri| acc + tri.0 + tri.1 + tri.2)
+            / (tris.len() * 3) as f32;
+
+        let mut shared_edges = 0;
+        for (i, a) in tris.iter().enumerate() {
+            for b in &tris[i + 1..] {
+                let shared: Vec<Vec3> = [a.0, a.1, a.2]
+                    .into_iter()
+                    .filter(|p| [b.0, b.1, b.2].contains(p))
+                    .collect();
+                let [e1, e2] = shared[..] else {
+                    continue;
+                };
+                shared_edges += 1;
+
+                let base = center + ((e1 + e2) / 2.0 - center) * 4.0;
+                for step in 1..64 {
+                    let target = e1 + (e2 - e1) * (step as f32 / 64.0);
+                    let ray = target - base;
+                    assert_ne!(
+                        Camera::calc_tri_raycast(base, ray, *a).is_some(),
+                        Camera::calc_tri_raycast(base, ray, *b).is_some(),
+                        "ray hit both or neither triangle at {target:?}",
+                    );
+                }
+            }
+        }
+        assert!(shared_edges > 0);
     }
 }
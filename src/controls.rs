@@ -0,0 +1,474 @@
+use crate::{
+    bindings::{Action, Bindings},
+    camera::Camera,
+    math::{Quat, Vec3},
+    world::World,
+};
+
+use winit::window::{CursorGrabMode, Window};
+use winit_input_helper::WinitInputHelper;
+
+/// Speed of movement and focal length changes (in units per second)
+const MOVE_SPEED: f32 = 3.0;
+/// Speed of rotation (in radians per second)
+const TURN_SPEED: f32 = std::f32::consts::FRAC_PI_2;
+/// Factor speeds are scaled by while shift is held
+const FAST_MULTIPLIER: f32 = 4.0;
+/// Factor speeds are scaled by while control is held
+const SLOW_MULTIPLIER: f32 = 0.25;
+/// Time (in seconds) for velocities to get about two thirds of the way to the speed the held keys
+/// ask for, so that the camera eases in and out of moving
+const SMOOTHING: f32 = 0.08;
+/// Velocities below this share of their full speed are snapped to zero once no key is held, so
+/// that the camera comes to rest instead of creeping forever
+const REST_THRESHOLD: f32 = 1e-3;
+/// Furthest the orbit camera tilts above or below the pivot (in radians), short of straight up or
+/// down where its yaw would flip
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+/// Closest the orbit camera gets to the pivot
+const MIN_DISTANCE: f32 = 0.01;
+
+/// How keys move the camera
+pub enum Controls {
+    Fly(FlyControls),
+    Orbit(OrbitControls),
+}
+
+impl Controls {
+    /// Move `camera` for `dt` seconds passing with the keys of `input` held. Returns whether the
+    /// camera changed.
+    pub fn update(
+        &mut self,
+        input: &WinitInputHelper,
+        bindings: &Bindings,
+        camera: &mut Camera,
+        dt: f32,
+    ) -> bool {
+        match self {
+            Self::Fly(controls) => controls.update(input, bindings, camera, dt),
+            Self::Orbit(controls) => controls.update(input, bindings, camera, dt),
+        }
+    }
+}
+
+/// Free-flying camera controls, moving and turning the camera relative to its own orientation
+/// while keys are held
+#[derive(Default)]
+pub struct FlyControls {
+    /// Velocity along the camera's own axes (in units per second)
+    velocity: Vec3,
+    /// Rotation around the camera's own axes (in radians per second)
+    angular_velocity: Vec3,
+    /// Change of the focal length (in units per second)
+    zoom_velocity: f32,
+    /// Forward movement that also shortens the focal length by as much (in units per second)
+    dolly_velocity: f32,
+}
+
+impl FlyControls {
+    /// Move `camera` for `dt` seconds passing with the keys of `input` held. Returns whether the
+    /// camera changed.
+    pub fn update(
+        &mut self,
+        input: &WinitInputHelper,
+        bindings: &Bindings,
+        camera: &mut Camera,
+        dt: f32,
+    ) -> bool {
+        let multiplier = if input.held_shift() {
+            FAST_MULTIPLIER
+        } else if input.held_control() {
+            SLOW_MULTIPLIER
+        } else {
+            1.0
+        };
+        let held_sum = |actions: &[(Action, Vec3)]| {
+            actions
+                .iter()
+                .filter(|&&(action, _)| bindings.held(input, action))
+                .fold(Vec3::default(), |sum, &(_, axis)| sum + axis)
+        };
+        let held = |action| {
+            if bindings.held(input, action) {
+                1.0
+            } else {
+                0.0
+            }
+        };
+
+        let target_velocity = held_sum(&[
+            (Action::MoveForward, Vec3::J),
+            (Action::MoveBackward, -Vec3::J),
+            (Action::MoveRight, Vec3::I),
+            (Action::MoveLeft, -Vec3::I),
+            (Action::MoveUp, Vec3::K),
+            (Action::MoveDown, -Vec3::K),
+        ]) * (MOVE_SPEED * multiplier);
+        let target_angular_velocity = held_sum(&[
+            (Action::LookLeft, Vec3::K),
+            (Action::LookRight, -Vec3::K),
+            (Action::LookUp, Vec3::I),
+            (Action::LookDown, -Vec3::I),
+            (Action::RollRight, Vec3::J),
+            (Action::RollLeft, -Vec3::J),
+        ]) * (TURN_SPEED * multiplier);
+        let target_zoom = (held(Action::IncreaseFocalLength) - held(Action::DecreaseFocalLength))
+            * MOVE_SPEED
+            * multiplier;
+        let target_dolly =
+            (held(Action::DollyOut) - held(Action::DollyIn)) * MOVE_SPEED * multiplier;
+
+        // Exponential approach, which does not depend on how finely time is divided into updates
+        let blend = 1.0 - (-dt / SMOOTHING).exp();
+        let min_speed = MOVE_SPEED * REST_THRESHOLD;
+        self.velocity = approach(self.velocity, target_velocity, blend, min_speed);
+        self.angular_velocity = approach(
+            self.angular_velocity,
+            target_angular_velocity,
+            blend,
+            TURN_SPEED * REST_THRESHOLD,
+        );
+        self.zoom_velocity = approach_speed(self.zoom_velocity, target_zoom, blend, min_speed);
+        self.dolly_velocity = approach_speed(self.dolly_velocity, target_dolly, blend, min_speed);
+
+        let rotation = camera.transform.rotation;
+        camera.transform.position +=
+            (self.velocity + Vec3::J * self.dolly_velocity).rotate(rotation) * dt;
+        camera.focal_length += (self.zoom_velocity - self.dolly_velocity) * dt;
+
+        let turn = self.angular_velocity * dt;
+        if turn != Vec3::default() {
+            let rot = &mut camera.transform.rotation;
+            *rot = normalized(Quat::rotation(turn.normalize().rotate(*rot), turn.mag())) * *rot;
+        }
+
+        self.is_moving()
+    }
+
+    fn is_moving(&self) -> bool {
+        self.velocity != Vec3::default()
+            || self.angular_velocity != Vec3::default()
+            || self.zoom_velocity != 0.0
+            || self.dolly_velocity != 0.0
+    }
+}
+
+/// Turntable controls, circling the camera around a pivot while keeping it pointed at it
+pub struct OrbitControls {
+    pivot: Vec3,
+    /// Angle around the vertical axis through the pivot (in radians), zero when looking along
+    /// the y axis
+    yaw: f32,
+    /// Angle above the horizontal plane through the pivot (in radians)
+    pitch: f32,
+    distance: f32,
+    /// Change of `yaw` and `pitch` (in radians per second)
+    angular_velocity: (f32, f32),
+    /// Change of the logarithm of `distance` (per second), so that zooming slows down close to
+    /// the pivot instead of passing it
+    zoom_velocity: f32,
+}
+
+impl OrbitControls {
+    /// Orbit around `pivot` starting from where `camera` is, turned to face the pivot
+    pub fn around(pivot: Vec3, camera: &Camera) -> Self {
+        let offset = camera.transform.position - pivot;
+        let distance = offset.mag().max(MIN_DISTANCE);
+        // Facing the pivot from straight behind the camera's view if it sits on the pivot
+        let forward = if offset.mag() < MIN_DISTANCE {
+            Vec3::J.rotate(camera.transform.rotation)
+        } else {
+            -offset / offset.mag()
+        };
+        Self {
+            pivot,
+            yaw: (-forward.x).atan2(forward.y),
+            pitch: (-forward.z).asin().clamp(-MAX_PITCH, MAX_PITCH),
+            distance,
+            angular_velocity: (0.0, 0.0),
+            zoom_velocity: 0.0,
+        }
+    }
+
+    /// Orbit around the center of the bounded objects of `world`, or the origin if there are
+    /// none
+    pub fn around_scene(world: &World, camera: &Camera) -> Self {
+        let bounds = world.bounds();
+        let pivot = if bounds.is_finite() {
+            bounds.centroid()
+        } else {
+            Vec3::default()
+        };
+        Self::around(pivot, camera)
+    }
+
+    /// Orbit for `dt` seconds passing with the keys of `input` held, reusing those that move the
+    /// camera when flying. Returns whether the camera changed.
+    pub fn update(
+        &mut self,
+        input: &WinitInputHelper,
+        bindings: &Bindings,
+        camera: &mut Camera,
+        dt: f32,
+    ) -> bool {
+        let multiplier = if input.held_shift() {
+            FAST_MULTIPLIER
+        } else if input.held_control() {
+            SLOW_MULTIPLIER
+        } else {
+            1.0
+        };
+        let axis = |positive, negative| {
+            let held = |action| {
+                if bindings.held(input, action) {
+                    1.0
+                } else {
+                    0.0
+                }
+            };
+            (held(positive) - held(negative)) * multiplier
+        };
+        let target_yaw = axis(Action::MoveRight, Action::MoveLeft) * TURN_SPEED;
+        let target_pitch = axis(Action::MoveUp, Action::MoveDown) * TURN_SPEED;
+        let target_zoom = axis(Action::MoveBackward, Action::MoveForward);
+
+        let blend = 1.0 - (-dt / SMOOTHING).exp();
+        let min_speed = TURN_SPEED * REST_THRESHOLD;
+        let (yaw_velocity, pitch_velocity) = self.angular_velocity;
+        self.angular_velocity = (
+            approach_speed(yaw_velocity, target_yaw, blend, min_speed),
+            approach_speed(pitch_velocity, target_pitch, blend, min_speed),
+        );
+        self.zoom_velocity = approach_speed(self.zoom_velocity, target_zoom, blend, REST_THRESHOLD);
+
+        self.yaw += self.angular_velocity.0 * dt;
+        self.pitch = (self.pitch + self.angular_velocity.1 * dt).clamp(-MAX_PITCH, MAX_PITCH);
+        self.distance = (self.distance * (self.zoom_velocity * dt).exp()).max(MIN_DISTANCE);
+        self.place(camera);
+
+        self.angular_velocity != (0.0, 0.0) || self.zoom_velocity != 0.0
+    }
+
+    /// Put `camera` on the orbit, looking at the pivot
+    pub fn place(&self, camera: &mut Camera) {
+        let rotation = Quat::rotation(Vec3::K, self.yaw) * Quat::rotation(Vec3::I, -self.pitch);
+        camera.transform.rotation = normalized(rotation);
+        camera.transform.position = self.pivot - Vec3::J.rotate(rotation) * self.distance;
+    }
+}
+
+/// How the mouse turns the camera
+#[derive(Clone, Copy, Debug)]
+pub struct MouseSettings {
+    /// Rotation per pixel the mouse moves (in radians)
+    pub sensitivity: f32,
+    /// Whether moving the mouse up looks down, like a flight stick
+    pub invert_y: bool,
+}
+
+impl Default for MouseSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 0.003,
+            invert_y: false,
+        }
+    }
+}
+
+/// Mouse controls: looking around while the cursor is grabbed, orbiting while the right button
+/// is held, and zooming with the scroll wheel
+pub struct MouseControls {
+    pub settings: MouseSettings,
+    /// Whether the cursor is grabbed, so that moving the mouse looks around
+    grabbed: bool,
+    /// Point orbited around while the right button is held
+    orbit_target: Option<Vec3>,
+}
+
+impl MouseControls {
+    pub const fn new(settings: MouseSettings) -> Self {
+        Self {
+            settings,
+            grabbed: false,
+            orbit_target: None,
+        }
+    }
+
+    /// Update `camera` for the mouse having moved by `motion` (in pixels) since the last update.
+    /// Clicking into `window` grabs the cursor and the key bound to releasing it does. Returns
+    /// whether the camera changed.
+    pub fn update(
+        &mut self,
+        input: &WinitInputHelper,
+        bindings: &Bindings,
+        window: &Window,
+        world: &World,
+        camera: &mut Camera,
+        motion: (f32, f32),
+    ) -> bool {
+        /// Button indices used by `WinitInputHelper`
+        const LEFT: usize = 0;
+        const RIGHT: usize = 1;
+        /// Factor the focal length is scaled by per line scrolled
+        const ZOOM_PER_LINE: f32 = 1.1;
+        /// Distance of the point orbited around when nothing is at the center of the view
+        const ORBIT_DIST: f32 = 2.0;
+
+        if input.mouse_pressed(LEFT) && !self.grabbed {
+            // Locking keeps the cursor in place, but not every platform supports it
+            let grab = window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(err) = grab {
+                eprintln!("failed to grab the cursor: {err}");
+            } else {
+                window.set_cursor_visible(false);
+                self.grabbed = true;
+            }
+        }
+        if bindings.pressed(input, Action::ReleaseCursor) {
+            self.release(window);
+        }
+
+        let mut changed = false;
+        let scroll = input.scroll_diff();
+        if scroll != 0.0 {
+            camera.focal_length *= ZOOM_PER_LINE.powf(scroll);
+            changed = true;
+        }
+
+        let (dx, dy) = motion;
+        let dy = if self.settings.invert_y { -dy } else { dy };
+        let (yaw, pitch) = (
+            -dx * self.settings.sensitivity,
+            -dy * self.settings.sensitivity,
+        );
+
+        if input.mouse_held(RIGHT) {
+            let target = *self.orbit_target.get_or_insert_with(|| {
+                camera.pick(world, 0.0, 0.0).unwrap_or_else(|| {
+                    let forward = Vec3::J.rotate(camera.transform.rotation);
+                    camera.transform.position + forward * ORBIT_DIST
+                })
+            });
+            if (dx, dy) != (0.0, 0.0) {
+                // Turning around the target rather than in place, so that the scene follows the
+                // mouse like it was dragged
+                let turn = turn(camera, yaw, pitch);
+                let transform = &mut camera.transform;
+                transform.position = target + (transform.position - target).rotate(turn);
+                transform.rotation = normalized(turn * transform.rotation);
+                changed = true;
+            }
+        } else {
+            self.orbit_target = None;
+            if self.grabbed && (dx, dy) != (0.0, 0.0) {
+                let turn = turn(camera, yaw, pitch);
+                camera.transform.rotation = normalized(turn * camera.transform.rotation);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Give the cursor back if it is grabbed
+    pub fn release(&mut self, window: &Window) {
+        if self.grabbed {
+            // Nothing can be done about failing to release it, and it is released on exit anyway
+            let _ = window.set_cursor_grab(CursorGrabMode::None);
+            window.set_cursor_visible(true);
+            self.grabbed = false;
+        }
+    }
+}
+
+/// Rotation turning `camera` by `yaw` around the world's vertical axis, after `pitch` around the
+/// camera's horizontal axis
+fn turn(camera: &Camera, yaw: f32, pitch: f32) -> Quat {
+    let right = Vec3::I.rotate(camera.transform.rotation);
+    Quat::rotation(Vec3::K, yaw) * Quat::rotation(right, pitch)
+}
+
+/// `rot` scaled back to a magnitude of 1. Mathematically, the magnitude should always remain at 1
+/// already, but floating point precision errors may cause self-fueleing inaccuracy that becomes
+/// worse with each rotation.
+fn normalized(rot: Quat) -> Quat {
+    if (rot.sq_mag() - 1.0).abs() > f32::EPSILON {
+        rot * rot.mag().recip()
+    } else {
+        rot
+    }
+}
+
+/// Move `current` towards `target` by the share `blend` of the way, coming to rest once `target`
+/// is zero and `current` is slower than `min_speed`
+fn approach(current: Vec3, target: Vec3, blend: f32, min_speed: f32) -> Vec3 {
+    let next = current + (target - current) * blend;
+    if target == Vec3::default() && next.mag() < min_speed {
+        Vec3::default()
+    } else {
+        next
+    }
+}
+
+/// [`approach`] for a speed along a single direction
+fn approach_speed(current: f32, target: f32, blend: f32, min_speed: f32) -> f32 {
+    let next = current + (target - current) * blend;
+    if target == 0.0 && next.abs() < min_speed {
+        0.0
+    } else {
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Transform;
+
+    #[test]
+    fn approach_is_frame_rate_independent() {
+        let target = Vec3::new(3.0, 0.0, 0.0);
+        let blend = |dt: f32| 1.0 - (-dt / SMOOTHING).exp();
+        let coarse = approach(Vec3::default(), target, blend(0.1), 0.0);
+        let mut fine = Vec3::default();
+        for _ in 0..10 {
+            fine = approach(fine, target, blend(0.01), 0.0);
+        }
+        assert!((coarse - fine).mag() < 1e-5);
+    }
+
+    #[test]
+    fn comes_to_rest() {
+        let slow = Vec3::new(1e-4, 0.0, 0.0);
+        assert_eq!(approach(slow, Vec3::default(), 0.1, 1e-3), Vec3::default());
+        // Not while speeding up from a standstill, however small the step
+        assert!(approach(Vec3::default(), Vec3::I, 1e-6, 1e-3).x > 0.0);
+        // Snapped to rest exactly rather than left arbitrarily close
+        assert_eq!(
+            approach_speed(-1e-4, 0.0, 0.1, 1e-3).to_bits(),
+            0.0f32.to_bits()
+        );
+    }
+
+    #[test]
+    fn orbit_looks_at_pivot() {
+        let pivot = Vec3::new(1.0, -2.0, 0.5);
+        let mut camera = Camera {
+            transform: Transform {
+                position: Vec3::new(4.0, 1.0, 3.0),
+                rotation: Quat::ONE,
+                scale: 1.0,
+            },
+            px_per_unit: 1.0,
+            focal_length: 1.0,
+        };
+        let start = camera.transform.position;
+        let orbit = OrbitControls::around(pivot, &camera);
+        orbit.place(&mut camera);
+        // Keeps its place, only turning to face the pivot
+        assert!((camera.transform.position - start).mag() < 1e-4);
+        let forward = Vec3::J.rotate(camera.transform.rotation);
+        assert!((forward - (pivot - start) / (pivot - start).mag()).mag() < 1e-4);
+    }
+}
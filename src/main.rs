@@ -14,25 +14,42 @@
     clippy::cast_lossless
 )]
 
+mod bindings;
+mod brdf;
+mod bvh;
 mod camera;
+mod controls;
 mod math;
+mod mesh;
+mod output;
+mod overlay;
+mod progress;
+mod sdf;
+mod texture;
+mod tiles;
 mod world;
 
-use camera::Camera;
+use bindings::{Action, Bindings, EXIT_KEY};
+use camera::{Aov, Camera, RayCounts, Visualization};
+use controls::{Controls, FlyControls, MouseControls, MouseSettings, OrbitControls};
 use math::{Quat, Vec3};
-use world::{Transform, World};
+use output::Format;
+use progress::Progress;
+use tiles::Tiling;
+use world::{Color, Transform, World};
 
-use pixels::{PixelsBuilder, SurfaceTexture};
-use rayon::prelude::*;
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
 use std::{
     collections::VecDeque,
-    time::{Duration, Instant},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use winit::{
     dpi::LogicalSize,
-    event::{Event, VirtualKeyCode},
+    event::{DeviceEvent, Event, VirtualKeyCode},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Window, WindowBuilder},
 };
 use winit_input_helper::WinitInputHelper;
 
@@ -43,20 +60,207 @@ const HALF_DIMS: (f32, f32) = (DIMS.0 as f32 / 2.0, DIMS.1 as f32 / 2.0);
 /// Number of frames used to create average
 const N_FRAMES: usize = 20;
 
+/// Longest time (in seconds) the camera moves for in one update, so that a stall does not send it
+/// flying
+const MAX_STEP: f32 = 0.25;
+
+/// Time between showing the tiles done so far, for frames that take longer than this to render
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Key bindings read when no other file is given
+const BINDINGS_PATH: &str = "bindings.ron";
+
+/// Factor the window dimensions are scaled by for high resolution screenshots
+const SCREENSHOT_SCALE: u32 = 2;
+/// Rays per pixel along each axis for high resolution screenshots
+const SCREENSHOT_SAMPLES: u32 = 3;
+
+/// Options given on the command line
+#[derive(Default)]
+struct Args {
+    /// Render a single frame to this file instead of opening the viewer
+    output: Option<PathBuf>,
+    /// Overrides the format implied by the extension of `output`
+    format: Option<Format>,
+    /// Auxiliary buffers written along with the beauty
+    aovs: Vec<Aov>,
+    tiling: Tiling,
+    mouse: MouseSettings,
+    /// Key bindings file, `bindings.ron` in the working directory being read if it exists
+    bindings: Option<PathBuf>,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Self::default();
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            let mut value = || {
+                iter.next()
+                    .ok_or_else(|| format!("missing value for `{arg}`"))
+            };
+            match arg.as_str() {
+                "-o" | "--output" => args.output = Some(value()?.into()),
+                "--format" => args.format = Some(value()?.parse()?),
+                "--tile-size" => {
+                    args.tiling.size = value()?
+                        .parse::<NonZeroU32>()
+                        .map_err(|err| format!("invalid tile size: {err}"))?
+                        .get();
+                }
+                "--tile-order" => args.tiling.order = value()?.parse()?,
+                "--mouse-sensitivity" => {
+                    args.mouse.sensitivity = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid mouse sensitivity: {err}"))?;
+                }
+                "--invert-y" => args.mouse.invert_y = true,
+                "--bindings" => args.bindings = Some(value()?.into()),
+                "--aovs" => {
+                    args.aovs = value()?
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(format!("unexpected argument `{arg}`")),
+            }
+        }
+        Ok(args)
+    }
+}
+
 fn main() {
-    let world = ron::from_str::<World>(include_str!("../scenes/cube.ron"))
-        .expect("failed to parse World file");
+    let args = Args::parse().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    });
+    let world =
+        World::load(include_str!("../scenes/cube.ron")).expect("failed to parse World file");
     let mut camera = Camera {
         transform: Transform {
             position: -0.8 * Vec3::J,
             rotation: Quat::ONE,
+            scale: 1.0,
         },
         px_per_unit: 160.0,
         focal_length: 2.0,
     };
 
+    if let Some(path) = &args.output {
+        if let Err(err) = render_to_file(path, &args, &world, &camera) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let bindings = load_bindings(args.bindings.as_deref()).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    });
+    eprintln!(
+        "Press {} for a list of controls",
+        bindings.key_name(Action::ToggleHelp)
+    );
+
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
+    let (window, mut pixels) = create_window(&event_loop);
+
+    let mut frametime_log: VecDeque<Duration> = VecDeque::with_capacity(N_FRAMES);
+    let mut viewer = Viewer {
+        tiling: args.tiling,
+        aov: Aov::Beauty,
+        vis: Visualization::Shaded,
+        show_help: false,
+        pending_screenshot: None,
+        controls: Controls::Fly(FlyControls::default()),
+        mouse_controls: MouseControls::new(args.mouse),
+        mouse_motion: (0.0, 0.0),
+        last_update: Instant::now(),
+    };
+
+    event_loop.run(move |event, _, control_flow| {
+        if let Event::DeviceEvent {
+            event: DeviceEvent::MouseMotion { delta },
+            ..
+        } = event
+        {
+            viewer.mouse_motion.0 += delta.0;
+            viewer.mouse_motion.1 += delta.1;
+        }
+        let updated = input.update(&event);
+        let switched = updated
+            && handle_actions(
+                &input,
+                &bindings,
+                &window,
+                &world,
+                &pixels,
+                &mut camera,
+                &mut viewer,
+            );
+        let keyboard_input: bool = updated
+            && handle_input(
+                &input,
+                &bindings,
+                control_flow,
+                &window,
+                &world,
+                &mut camera,
+                &mut viewer,
+            );
+
+        let redraw_requested: bool = matches!(event, Event::RedrawRequested(_));
+
+        // Draw the current frame
+        if keyboard_input || switched || redraw_requested {
+            do_render(
+                &mut pixels,
+                viewer.tiling,
+                &world,
+                &camera,
+                (viewer.aov, viewer.vis),
+                Some(&mut frametime_log),
+            );
+            if let Some(path) = viewer.pending_screenshot.take() {
+                report_screenshot(&path, output::save_rgba8(&path, DIMS, pixels.get_frame()));
+            }
+            if viewer.show_help {
+                overlay::draw_text(pixels.get_frame_mut(), DIMS.0 as usize, &bindings.help());
+            }
+            if pixels
+                .render()
+                .map_err(|e| panic!("pixels.render() failed: {e}"))
+                .is_err()
+            {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+    });
+}
+
+/// Render the beauty and the auxiliary buffers asked for by `args` to `path`
+fn render_to_file(path: &Path, args: &Args, world: &World, camera: &Camera) -> Result<(), String> {
+    let buffers: Vec<_> = std::iter::once(Aov::Beauty)
+        .chain(args.aovs.iter().copied().filter(|&aov| aov != Aov::Beauty))
+        .map(|aov| (aov, render_hdr(world, camera, DIMS, (1, args.tiling), aov)))
+        .collect();
+    let format = args.format.map_or_else(|| Format::from_path(path), Ok)?;
+    output::save_aovs(path, format, DIMS, &buffers)
+}
+
+/// Key bindings from `path`, or from `BINDINGS_PATH` if it exists when no path is given
+fn load_bindings(path: Option<&Path>) -> Result<Bindings, String> {
+    match path {
+        Some(path) => Bindings::load(path),
+        None if Path::new(BINDINGS_PATH).exists() => Bindings::load(Path::new(BINDINGS_PATH)),
+        None => Ok(Bindings::default()),
+    }
+}
+
+/// Window of the viewer, with a frame of `DIMS` drawn to it
+fn create_window(event_loop: &EventLoop<()>) -> (Window, Pixels) {
     let window = {
         let size = LogicalSize::new(DIMS.0, DIMS.1);
         WindowBuilder::new()
@@ -64,7 +268,7 @@ fn main() {
             .with_inner_size(size)
             .with_min_inner_size(size)
             .with_decorations(false) // weird graphical issue happens without this (at least on gnome + wayland) further investigation needed
-            .build(&event_loop)
+            .build(event_loop)
             .expect("WindowBuilder failed")
     };
 
@@ -78,137 +282,174 @@ fn main() {
     };
     // Fill alpha channel to avoid setting it later
     pixels.get_frame_mut().fill(0xff);
+    (window, pixels)
+}
 
-    let mut frametime_log: VecDeque<Duration> = VecDeque::with_capacity(N_FRAMES);
-
-    event_loop.run(move |event, _, control_flow| {
-        let keyboard_input: bool =
-            input.update(&event) && handle_input(&input, control_flow, &mut camera);
-
-        let redraw_requested: bool = matches!(event, Event::RedrawRequested(_));
+/// State of the viewer changed by the keys bound to actions
+struct Viewer {
+    tiling: Tiling,
+    /// Buffer shown in the window, unless a diagnostic view is chosen
+    aov: Aov,
+    vis: Visualization,
+    /// Overlay listing what the keys do
+    show_help: bool,
+    /// Screenshot of the window to save once the next frame is rendered, before the help is
+    /// drawn over it
+    pending_screenshot: Option<PathBuf>,
+    controls: Controls,
+    mouse_controls: MouseControls,
+    /// Mouse motion since the last update, which keeps coming while the cursor is grabbed
+    mouse_motion: (f64, f64),
+    last_update: Instant,
+}
 
-        // Draw the current frame
-        if keyboard_input || redraw_requested {
-            do_render(
-                pixels.get_frame_mut(),
-                &world,
-                &camera,
-                Some(&mut frametime_log),
-            );
-            if pixels
-                .render()
-                .map_err(|e| panic!("pixels.render() failed: {e}"))
-                .is_err()
-            {
-                *control_flow = ControlFlow::Exit;
-            }
+/// Carry out the actions whose keys were pressed, other than those moving the camera. Returns
+/// whether the frame has to be rendered again.
+fn handle_actions(
+    input: &WinitInputHelper,
+    bindings: &Bindings,
+    window: &Window,
+    world: &World,
+    pixels: &Pixels,
+    camera: &mut Camera,
+    viewer: &mut Viewer,
+) -> bool {
+    let mut switched = false;
+    if bindings.pressed(input, Action::Screenshot) {
+        let path = screenshot_path();
+        // Shift re-renders the view at a higher resolution and sample count than the window
+        if input.held_shift() {
+            let dims = (DIMS.0 * SCREENSHOT_SCALE, DIMS.1 * SCREENSHOT_SCALE);
+            let camera = Camera {
+                px_per_unit: camera.px_per_unit * SCREENSHOT_SCALE as f32,
+                ..*camera
+            };
+            let samples = (SCREENSHOT_SAMPLES, viewer.tiling);
+            let frame = render_hdr(world, &camera, dims, samples, Aov::Beauty);
+            report_screenshot(&path, output::save(&path, Format::Png, dims, &frame));
+        } else if viewer.show_help {
+            viewer.pending_screenshot = Some(path);
+            switched = true;
+        } else {
+            report_screenshot(&path, output::save_rgba8(&path, DIMS, pixels.get_frame()));
         }
-    });
+    }
+    if bindings.pressed(input, Action::CycleAov) {
+        viewer.aov = viewer.aov.next();
+        viewer.vis = Visualization::Shaded;
+        eprintln!("Showing {}", viewer.aov.name());
+        switched = true;
+    }
+    if bindings.pressed(input, Action::CycleVisualization) {
+        viewer.vis = viewer.vis.next();
+        viewer.aov = Aov::Beauty;
+        eprintln!("Showing {}", viewer.vis.name());
+        switched = true;
+    }
+    // Orbiting is set up around the scene as it is when switched to, turning the camera to it
+    if bindings.pressed(input, Action::ToggleOrbit) {
+        viewer.controls = match viewer.controls {
+            Controls::Fly(_) => {
+                viewer.mouse_controls.release(window);
+                let orbit = OrbitControls::around_scene(world, camera);
+                orbit.place(camera);
+                eprintln!("Orbiting the scene");
+                Controls::Orbit(orbit)
+            }
+            Controls::Orbit(_) => {
+                eprintln!("Flying freely");
+                Controls::Fly(FlyControls::default())
+            }
+        };
+        switched = true;
+    }
+    if bindings.pressed(input, Action::ToggleHelp) {
+        viewer.show_help = !viewer.show_help;
+        switched = true;
+    }
+    switched
+}
+
+fn report_screenshot(path: &Path, saved: Result<(), String>) {
+    match saved {
+        Ok(()) => eprintln!("Saved screenshot to `{}`", path.display()),
+        Err(err) => eprintln!("{err}"),
+    }
 }
 
+/// Move the camera with the keys and the mouse. Returns whether it changed.
 fn handle_input(
     input: &WinitInputHelper,
+    bindings: &Bindings,
     control_flow: &mut ControlFlow,
+    window: &Window,
+    world: &World,
     camera: &mut Camera,
+    viewer: &mut Viewer,
 ) -> bool {
     if (input.key_held(VirtualKeyCode::LControl) || input.key_held(VirtualKeyCode::RControl))
-        && input.key_pressed(VirtualKeyCode::C)
+        && input.key_pressed(EXIT_KEY)
     {
         *control_flow = ControlFlow::Exit;
     }
-    const DELTA: f32 = 0.015;
-    const MOVE_SPEED: f32 = 3.0 * DELTA;
-    const TURN_SPEED: f32 = std::f32::consts::FRAC_PI_2 * DELTA;
-    let mut did_movement: bool = false;
-    let mut movement = |delta: Vec3| {
-        camera.transform.position += delta.rotate(camera.transform.rotation);
-        did_movement = true;
-    };
-    [
-        (VirtualKeyCode::W, Vec3::J),
-        (VirtualKeyCode::S, -Vec3::J),
-        (VirtualKeyCode::D, Vec3::I),
-        (VirtualKeyCode::A, -Vec3::I),
-        (VirtualKeyCode::E, Vec3::K),
-        (VirtualKeyCode::Q, -Vec3::K),
-    ]
-    .into_iter()
-    .filter_map(|(key, axis)| input.key_held(key).then_some(axis))
-    .for_each(|axis| movement(MOVE_SPEED * axis));
-    if input.key_held(VirtualKeyCode::X) {
-        movement(MOVE_SPEED * Vec3::J);
-        camera.focal_length -= MOVE_SPEED;
-    }
-    if input.key_held(VirtualKeyCode::Z) {
-        movement(-MOVE_SPEED * Vec3::J);
-        camera.focal_length += MOVE_SPEED;
-    }
-    if input.key_held(VirtualKeyCode::R) {
-        camera.focal_length += MOVE_SPEED;
-        did_movement = true;
-    }
-    if input.key_held(VirtualKeyCode::F) {
-        camera.focal_length -= MOVE_SPEED;
-        did_movement = true;
-    }
-    let mut did_rotation: bool = false;
-    let mut rotation = |angle: f32, axis: Vec3| {
-        let rot = &mut camera.transform.rotation;
-        let mut new_rot = Quat::rotation(axis.rotate(*rot), angle);
-
-        // Mathematically, the magnitude should always remain at 1 already, but floating point
-        // precision errors may cause self-fueleing inaccuracy that becomes worse with each rotation.
-        if (new_rot.sq_mag() - 1.0).abs() > f32::EPSILON {
-            new_rot = new_rot * new_rot.mag().recip();
-        }
+    // Long frames make for long steps rather than slow movement, up to a point
+    let dt = viewer.last_update.elapsed().as_secs_f32().min(MAX_STEP);
+    viewer.last_update = Instant::now();
+    let moved = viewer.controls.update(input, bindings, camera, dt);
 
-        *rot = new_rot * *rot;
-        did_rotation = true;
-    };
-    [
-        (VirtualKeyCode::J, Vec3::K),
-        (VirtualKeyCode::L, -Vec3::K),
-        (VirtualKeyCode::I, Vec3::I),
-        (VirtualKeyCode::K, -Vec3::I),
-        (VirtualKeyCode::O, Vec3::J),
-        (VirtualKeyCode::U, -Vec3::J),
-    ]
-    .into_iter()
-    .filter_map(|(key, axis)| input.key_held(key).then_some(axis))
-    .for_each(|axis| rotation(TURN_SPEED, axis));
-    did_rotation || did_movement
+    let (dx, dy) = std::mem::take(&mut viewer.mouse_motion);
+    #[allow(clippy::cast_possible_truncation)]
+    let motion = (dx as f32, dy as f32);
+    // The mouse turns the camera freely, which orbiting would undo straight away
+    let flying = matches!(viewer.controls, Controls::Fly(_));
+    let mouse = &mut viewer.mouse_controls;
+    (flying && mouse.update(input, bindings, window, world, camera, motion)) || moved
 }
 
 fn do_render(
-    frame: &mut [u8],
+    pixels: &mut Pixels,
+    tiling: Tiling,
     world: &World,
     camera: &Camera,
+    (aov, vis): (Aov, Visualization),
     frame_data: Option<&mut VecDeque<Duration>>,
 ) {
     // Create a instant here to time how long it takes to render a frame
     let now = Instant::now();
+    let mut last_shown = now;
+    let mut rays = RayCounts::default();
 
-    // Used to zip with frame data in place of enumerating (which cannot be done with par_chunks_exact_mut)
-    const INDEX: std::ops::Range<u32> = 0..(DIMS.0 * DIMS.1);
-
-    frame
-        .par_chunks_exact_mut(4)
-        .zip(INDEX)
-        .for_each(|(pixel, i)| {
-            // SAFETY: Pixel size will always be 4, RGBA
-            unsafe {
-                std::intrinsics::assume(pixel.len() == 4);
-            }
-
-            // (x, y) of pixel on screen
-            let (x, y): (u32, u32) = (i % DIMS.0, i / DIMS.0);
-
+    tiles::render(
+        tiling.tiles(DIMS),
+        |x, y| {
             let x_w = x as f32 - HALF_DIMS.0;
             let y_w = y as f32 - HALF_DIMS.1;
-            pixel[0..=2].copy_from_slice(&camera.get_px(world, x_w, y_w).0);
-        });
+            let color = if vis != Visualization::Shaded {
+                Color::from_rgb(camera.get_px_debug(world, x_w, y_w, vis))
+            } else if aov != Aov::Beauty {
+                Color::from_rgb(aov.display(camera.get_aov(world, x_w, y_w, aov)))
+            } else {
+                camera.get_px(world, x_w, y_w)
+            };
+            (color, RayCounts::take())
+        },
+        |tile, pixel_data| {
+            let frame = pixels.get_frame_mut();
+            for ((x, y), (color, pixel_rays)) in tile.pixels().zip(pixel_data) {
+                let i = 4 * (y * DIMS.0 + x) as usize;
+                frame[i..i + 3].copy_from_slice(&color.0);
+                rays += pixel_rays;
+            }
+            if last_shown.elapsed() >= PROGRESS_INTERVAL {
+                // Failures are reported when the finished frame is shown
+                let _ = pixels.render();
+                last_shown = Instant::now();
+            }
+        },
+    );
 
     let took = now.elapsed();
+    let rays_per_sec = progress::rays_per_sec(rays.total(), took);
 
     if let Some(frametime_log) = frame_data {
         // Only remove the last element if the queue is the desired size
@@ -221,8 +462,60 @@ fn do_render(
         #[allow(clippy::cast_possible_truncation)]
         let avg_frametime = frametime_log.iter().sum::<Duration>() / frametime_log.len() as u32;
 
-        eprintln!("Frame took: {took:#?} (avg: {avg_frametime:#?})");
+        eprintln!("Frame took: {took:#?} (avg: {avg_frametime:#?}, {rays_per_sec} rays/s)");
     } else {
-        eprintln!("Frame took: {took:#?}");
+        eprintln!("Frame took: {took:#?} ({rays_per_sec} rays/s)");
     }
 }
+
+/// Render the raw values of `aov` for a frame of `dims`, without clamping colors brighter than
+/// white, for writing to a file. Each pixel averages a grid of `samples` by `samples` rays spread
+/// over it.
+fn render_hdr(
+    world: &World,
+    camera: &Camera,
+    dims: (u32, u32),
+    (samples, tiling): (u32, Tiling),
+    aov: Aov,
+) -> Vec<[f32; 3]> {
+    let half_dims = (dims.0 as f32 / 2.0, dims.1 as f32 / 2.0);
+    // Position of a sample within its pixel, relative to the pixel's center
+    let offset = |n: u32| (n as f32 + 0.5) / samples as f32 - 0.5;
+
+    let tiles = tiling.tiles(dims);
+    let mut progress = Progress::new(aov.name(), tiles.len());
+    let mut frame = vec![[0.0; 3]; (dims.0 * dims.1) as usize];
+    tiles::render(
+        tiles,
+        |x, y| {
+            let mut rgb = [0.0; 3];
+            for (sx, sy) in (0..samples).flat_map(|sx| (0..samples).map(move |sy| (sx, sy))) {
+                let x_w = x as f32 + offset(sx) - half_dims.0;
+                let y_w = y as f32 + offset(sy) - half_dims.1;
+                let color = camera.get_aov(world, x_w, y_w, aov);
+                for (n, c) in rgb.iter_mut().zip(color) {
+                    *n += c / (samples * samples) as f32;
+                }
+            }
+            (rgb, RayCounts::take())
+        },
+        |tile, pixel_data| {
+            let mut rays = RayCounts::default();
+            for ((x, y), (value, pixel_rays)) in tile.pixels().zip(pixel_data) {
+                frame[(y * dims.0 + x) as usize] = value;
+                rays += pixel_rays;
+            }
+            progress.tile_done(rays);
+        },
+    );
+    progress.finish();
+    frame
+}
+
+/// File in the working directory named after the current time, for a screenshot
+fn screenshot_path() -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    PathBuf::from(format!("screenshot-{secs}.png"))
+}
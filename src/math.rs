@@ -141,9 +141,39 @@ impl Vec3 {
     pub const fn cross(self, rhs: Self) -> Self {
         Self::from(self * Quat::from(rhs))
     }
+
+    #[inline]
+    pub fn abs(self) -> Self {
+        Self::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
+    /// Two unit vectors perpendicular to this unit vector and to each other
+    pub fn orthonormal_basis(self) -> (Self, Self) {
+        let other = if self.x.abs() > 0.9 { Self::J } else { Self::I };
+        let side = self.cross(other).normalize();
+        (side, self.cross(side))
+    }
+
+    /// Component-wise minimum
+    #[inline]
+    pub fn min(self, rhs: Self) -> Self {
+        Self::new(self.x.min(rhs.x), self.y.min(rhs.y), self.z.min(rhs.z))
+    }
+
+    /// Component-wise maximum
+    #[inline]
+    pub fn max(self, rhs: Self) -> Self {
+        Self::new(self.x.max(rhs.x), self.y.max(rhs.y), self.z.max(rhs.z))
+    }
+
+    /// Largest component
+    #[inline]
+    pub fn max_elem(self) -> f32 {
+        self.x.max(self.y).max(self.z)
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
 pub struct Quat {
     pub r: f32,
     pub i: f32,
@@ -292,6 +322,135 @@ impl Quat {
     }
 }
 
+/// Ken Perlin's reference permutation of `0..=255`
+const PERMUTATION: [u8; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209, 76,
+    132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+/// Gradient noise in roughly `-1.0..=1.0`, which is zero at integer coordinates
+#[allow(clippy::many_single_char_names)]
+pub fn perlin(p: Vec3) -> f32 {
+    #[allow(clippy::cast_possible_truncation)]
+    let cell = |n: f32| (n.floor() as i32 & 255) as usize;
+    let perm = |i: usize| PERMUTATION[i & 255] as usize;
+    // Smootherstep, so that the noise has continuous second derivatives
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |t: f32, a: f32, b: f32| a + t * (b - a);
+    // Dot product of `(x, y, z)` with one of 12 gradients picked by `hash`
+    let grad = |hash: usize, x: f32, y: f32, z: f32| {
+        let h = hash & 15;
+        let u = if h < 8 { x } else { y };
+        let v = match h {
+            0..=3 => y,
+            12 | 14 => x,
+            _ => z,
+        };
+        (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+    };
+
+    let (xi, yi, zi) = (cell(p.x), cell(p.y), cell(p.z));
+    let (x, y, z) = (p.x - p.x.floor(), p.y - p.y.floor(), p.z - p.z.floor());
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    let a = perm(xi) + yi;
+    let (aa, ab) = (perm(a) + zi, perm(a + 1) + zi);
+    let b = perm(xi + 1) + yi;
+    let (ba, bb) = (perm(b) + zi, perm(b + 1) + zi);
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, grad(perm(aa), x, y, z), grad(perm(ba), x - 1.0, y, z)),
+            lerp(
+                u,
+                grad(perm(ab), x, y - 1.0, z),
+                grad(perm(bb), x - 1.0, y - 1.0, z),
+            ),
+        ),
+        lerp(
+            v,
+            lerp(
+                u,
+                grad(perm(aa + 1), x, y, z - 1.0),
+                grad(perm(ba + 1), x - 1.0, y, z - 1.0),
+            ),
+            lerp(
+                u,
+                grad(perm(ab + 1), x, y - 1.0, z - 1.0),
+                grad(perm(bb + 1), x - 1.0, y - 1.0, z - 1.0),
+            ),
+        ),
+    )
+}
+
+/// Octaves of noise beyond which layers add less than the precision of an `f32`, however many a
+/// scene asks for
+const MAX_OCTAVES: u32 = 24;
+
+/// Fractal Brownian motion: `octaves` layers of noise, each at twice the frequency and half the
+/// amplitude of the previous one
+pub fn fbm(p: Vec3, octaves: u32) -> f32 {
+    let mut scale = 1.0;
+    let mut sum = 0.0;
+    for _ in 0..octaves.min(MAX_OCTAVES) {
+        sum += perlin(p * scale) / scale;
+        scale *= 2.0;
+    }
+    sum
+}
+
+/// Like [`fbm`] but summing the absolute value of each layer, giving creases where the noise
+/// crosses zero
+pub fn turbulence(p: Vec3, octaves: u32) -> f32 {
+    let mut scale = 1.0;
+    let mut sum = 0.0;
+    for _ in 0..octaves.min(MAX_OCTAVES) {
+        sum += perlin(p * scale).abs() / scale;
+        scale *= 2.0;
+    }
+    sum
+}
+
+/// Small pseudo-random number generator (xorshift64*), for sampling
+pub struct Rng(u64);
+
+impl Rng {
+    /// Generator seeded from the bits of a point, so that the same point always sees the same
+    /// sequence
+    pub fn from_point(p: Vec3) -> Self {
+        let seed = [p.x, p.y, p.z]
+            .into_iter()
+            .fold(0x9e37_79b9_7f4a_7c15_u64, |acc, n| {
+                (acc ^ u64::from(n.to_bits())).wrapping_mul(0xbf58_476d_1ce4_e5b9)
+            });
+        // The state must never be zero
+        Self(seed | 1)
+    }
+
+    /// Next number, uniformly distributed in `0.0..1.0`
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        // The top 24 bits fit exactly in the mantissa
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 40) as f32 / (1 << 24) as f32
+    }
+}
+
 #[cfg(test)]
 mod vec3_tests {
     use super::*;
@@ -372,6 +531,21 @@ mod vec3_tests {
         let v_new: Vec3 = v.rotate(rot);
         assert!((v_new - Vec3::new(-1.0, 1.0, 1.0)).sq_mag() < f32::EPSILON);
     }
+
+    #[test]
+    fn abs() {
+        let a: Vec3 = Vec3::new(-1.0, 2.0, -3.0);
+        assert_eq!(a.abs(), Vec3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn min_max() {
+        let a: Vec3 = Vec3::new(1.0, 5.0, -3.0);
+        let b: Vec3 = Vec3::new(2.0, 4.0, -4.0);
+        assert_eq!(a.min(b), Vec3::new(1.0, 4.0, -4.0));
+        assert_eq!(a.max(b), Vec3::new(2.0, 5.0, -3.0));
+        assert!((a.max_elem() - 5.0).abs() < f32::EPSILON);
+    }
 }
 
 #[cfg(test)]
@@ -448,3 +622,51 @@ mod quat_tests {
         assert!((b - 30.0f32.sqrt()).abs() < f32::EPSILON);
     }
 }
+
+#[cfg(test)]
+mod noise_tests {
+    use super::*;
+
+    #[test]
+    fn perlin_lattice() {
+        for p in [
+            Vec3::default(),
+            Vec3::new(1.0, -3.0, 7.0),
+            Vec3::new(-20.0, 4.0, 300.0),
+        ] {
+            assert!(perlin(p).abs() < f32::EPSILON);
+        }
+    }
+
+    #[test]
+    fn perlin_range() {
+        for i in 0..1000 {
+            let i = i as f32;
+            let p = Vec3::new(i * 0.173, i * 0.311 - 50.0, i * 0.057);
+            let n = perlin(p);
+            assert!((-1.0..=1.0).contains(&n), "{n} out of range at {p:?}");
+        }
+    }
+
+    #[test]
+    fn perlin_continuous() {
+        let p = Vec3::new(0.999, 2.5, -1.3);
+        let q = Vec3::new(1.001, 2.5, -1.3);
+        assert!((perlin(p) - perlin(q)).abs() < 0.01);
+    }
+
+    #[test]
+    fn turbulence_positive() {
+        for i in 0..100 {
+            let i = i as f32;
+            assert!(turbulence(Vec3::new(i * 0.37, i * 0.11, 0.5), 4) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn many_octaves() {
+        let p = Vec3::new(0.3, 1.7, -2.1);
+        assert!(fbm(p, 40).is_finite());
+        assert!((turbulence(p, u32::MAX) - turbulence(p, MAX_OCTAVES)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{
+    bvh::{Aabb, Bvh},
+    math::Vec3,
+    world::Material,
+};
+
+use serde::Deserialize;
+
+/// Indexed triangle mesh, sharing vertices between its triangles
+#[derive(Deserialize)]
+#[serde(try_from = "MeshDef")]
+pub struct TriangleMesh {
+    pub vertices: Vec<Vec3>,
+    /// Indices into `vertices` of the points of each triangle
+    pub indices: Vec<[u32; 3]>,
+    /// Per-vertex shading normals, faces are flat shaded without them
+    pub normals: Option<Vec<Vec3>>,
+    /// Per-vertex texture coordinates
+    #[allow(dead_code)]
+    pub uvs: Option<Vec<(f32, f32)>>,
+    pub material: Material,
+    pub bvh: Bvh,
+}
+
+/// Scene file form of a [`TriangleMesh`], checked and given its BVH on conversion
+#[derive(Deserialize)]
+#[serde(rename = "TriangleMesh")]
+struct MeshDef {
+    vertices: Vec<Vec3>,
+    indices: Vec<[u32; 3]>,
+    #[serde(default)]
+    normals: Option<Vec<Vec3>>,
+    #[serde(default)]
+    uvs: Option<Vec<(f32, f32)>>,
+    material: Material,
+}
+
+impl TryFrom<MeshDef> for TriangleMesh {
+    type Error = String;
+
+    fn try_from(def: MeshDef) -> Result<Self, String> {
+        let n_vertices = def.vertices.len();
+        if let Some(index) = def
+            .indices
+            .iter()
+            .flatten()
+            .find(|&&i| i as usize >= n_vertices)
+        {
+            return Err(format!(
+                "mesh index {index} out of range for {n_vertices} vertices"
+            ));
+        }
+        for (name, len) in [
+            ("normals", def.normals.as_ref().map(Vec::len)),
+            ("uvs", def.uvs.as_ref().map(Vec::len)),
+        ] {
+            if let Some(len) = len.filter(|&len| len != n_vertices) {
+                return Err(format!("mesh has {len} {name} for {n_vertices} vertices"));
+            }
+        }
+
+        let mut mesh = Self {
+            vertices: def.vertices,
+            indices: def.indices,
+            normals: def.normals,
+            uvs: def.uvs,
+            material: def.material,
+            bvh: Bvh::default(),
+        };
+        let bounds: Vec<Aabb> = (0..mesh.indices.len())
+            .map(|i| Aabb::from_points(mesh.triangle(i)))
+            .collect();
+        mesh.bvh = Bvh::build(&bounds);
+        Ok(mesh)
+    }
+}
+
+impl TriangleMesh {
+    /// Points of the triangle at `index`
+    pub fn triangle(&self, index: usize) -> [Vec3; 3] {
+        self.indices[index].map(|i| self.vertices[i as usize])
+    }
+}
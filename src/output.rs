@@ -0,0 +1,272 @@
+use crate::camera::Aov;
+
+use exr::prelude::{
+    f16, AnyChannel, AnyChannels, Encoding, FlatSamples, Image, ImageAttributes, IntegerBounds,
+    Layer, LayerAttributes, WritableImage,
+};
+use image::{ColorType, DynamicImage, ImageBuffer, ImageFormat, Rgb};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Image encoding a rendered frame is written with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// 8-bit PNG
+    Png,
+    /// 16-bit PNG
+    Png16,
+    /// 8-bit binary PPM
+    Ppm,
+    /// 16-bit TIFF
+    Tiff16,
+    /// `OpenEXR` with half precision channels
+    ExrHalf,
+    /// `OpenEXR` with single precision channels
+    ExrFloat,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    /// Format from the name given to the `--format` flag
+    fn from_str(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Ok(Self::Png),
+            "png16" => Ok(Self::Png16),
+            "ppm" => Ok(Self::Ppm),
+            "tiff" | "tiff16" => Ok(Self::Tiff16),
+            "exr" | "exr-half" => Ok(Self::ExrHalf),
+            "exr-float" => Ok(Self::ExrFloat),
+            _ => Err(format!(
+                "unknown format `{name}`, expected one of png, png16, ppm, tiff, exr, exr-float"
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// Format implied by the extension of `path`. PNGs and EXRs get the smaller of their
+    /// precisions.
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match ext.as_deref() {
+            Some("png") => Ok(Self::Png),
+            Some("ppm") => Ok(Self::Ppm),
+            Some("tif" | "tiff") => Ok(Self::Tiff16),
+            Some("exr") => Ok(Self::ExrHalf),
+            _ => Err(format!(
+                "cannot tell the format of `{}` from its extension, pass --format",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// Write a `width` by `height` frame of unclamped, gamma encoded colors in row-major order.
+/// Integer formats clamp channels to white, while EXRs store the linear radiance with nothing
+/// clamped.
+pub fn save(
+    path: &Path,
+    format: Format,
+    (width, height): (u32, u32),
+    frame: &[[f32; 3]],
+) -> Result<(), String> {
+    assert_eq!(frame.len(), (width * height) as usize);
+    let err = |err: &dyn std::fmt::Display| format!("failed to write `{}`: {err}", path.display());
+
+    let image = match format {
+        Format::Png | Format::Ppm => DynamicImage::ImageRgb8(quantize(width, height, frame)),
+        Format::Png16 | Format::Tiff16 => DynamicImage::ImageRgb16(quantize(width, height, frame)),
+        Format::ExrHalf => {
+            return write_exr(path, width, frame, f16::from_f32).map_err(|e| err(&e));
+        }
+        Format::ExrFloat => return write_exr(path, width, frame, |n| n).map_err(|e| err(&e)),
+    };
+    let image_format = match format {
+        Format::Png | Format::Png16 => ImageFormat::Png,
+        Format::Ppm => ImageFormat::Pnm,
+        _ => ImageFormat::Tiff,
+    };
+    image
+        .save_with_format(path, image_format)
+        .map_err(|e| err(&e))
+}
+
+/// Write the beauty frame along with auxiliary buffers, each holding raw values as given by
+/// [`Camera::get_aov`](crate::camera::Camera::get_aov). EXRs get a single file with a layer per
+/// buffer. Other formats get a file per buffer, named like `render.depth.png` for `render.png`,
+/// with the values shown as [`Aov::display`] does.
+pub fn save_aovs(
+    path: &Path,
+    format: Format,
+    dims: (u32, u32),
+    buffers: &[(Aov, Vec<[f32; 3]>)],
+) -> Result<(), String> {
+    if matches!(format, Format::ExrHalf | Format::ExrFloat) {
+        return write_exr_layers(path, format, dims, buffers)
+            .map_err(|err| format!("failed to write `{}`: {err}", path.display()));
+    }
+    for (aov, frame) in buffers {
+        let frame: Vec<_> = frame.iter().map(|&value| aov.display(value)).collect();
+        save(&aov_path(path, *aov), format, dims, &frame)?;
+    }
+    Ok(())
+}
+
+/// Path of the file holding `aov` for renders written to `path`
+fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    if aov == Aov::Beauty {
+        return path.to_owned();
+    }
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(format!(".{}", aov.name()));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+/// Write an 8-bit RGBA frame, in the layout the viewer draws to, as a PNG
+pub fn save_rgba8(path: &Path, (width, height): (u32, u32), frame: &[u8]) -> Result<(), String> {
+    image::save_buffer_with_format(
+        path,
+        frame,
+        width,
+        height,
+        ColorType::Rgba8,
+        ImageFormat::Png,
+    )
+    .map_err(|err| format!("failed to write `{}`: {err}", path.display()))
+}
+
+/// Integer pixel with channels scaled so that `1.0` is the largest value of the type
+trait Quantize: image::Primitive + 'static {
+    fn quantize(n: f32) -> Self;
+}
+
+impl Quantize for u8 {
+    #[allow(clippy::cast_possible_truncation)]
+    fn quantize(n: f32) -> Self {
+        (n * 255.0).round().clamp(0.0, 255.0) as u8
+    }
+}
+
+impl Quantize for u16 {
+    #[allow(clippy::cast_possible_truncation)]
+    fn quantize(n: f32) -> Self {
+        (n * 65535.0).round().clamp(0.0, 65535.0) as u16
+    }
+}
+
+fn quantize<T: Quantize>(width: u32, height: u32, frame: &[[f32; 3]]) -> ImageBuffer<Rgb<T>, Vec<T>>
+where
+    Rgb<T>: image::Pixel<Subpixel = T>,
+{
+    ImageBuffer::from_fn(width, height, |x, y| {
+        Rgb(frame[(y * width + x) as usize].map(T::quantize))
+    })
+}
+
+fn write_exr<T: exr::prelude::IntoSample>(
+    path: &Path,
+    width: u32,
+    frame: &[[f32; 3]],
+    convert: impl Fn(f32) -> T + Sync,
+) -> exr::error::UnitResult {
+    let width = width as usize;
+    exr::prelude::write_rgb_file(path, width, frame.len() / width, |x, y| {
+        let [r, g, b] = frame[y * width + x].map(|n| convert(linearize(n)));
+        (r, g, b)
+    })
+}
+
+fn write_exr_layers(
+    path: &Path,
+    format: Format,
+    (width, height): (u32, u32),
+    buffers: &[(Aov, Vec<[f32; 3]>)],
+) -> exr::error::UnitResult {
+    let size = (width as usize, height as usize);
+    let layers: Vec<_> = buffers
+        .iter()
+        .map(|(aov, frame)| {
+            let channels = aov
+                .channels()
+                .iter()
+                .enumerate()
+                .map(|(i, &name)| {
+                    let values = frame.iter().map(|value| {
+                        if aov.is_color() {
+                            linearize(value[i])
+                        } else {
+                            value[i]
+                        }
+                    });
+                    let samples = if format == Format::ExrHalf {
+                        FlatSamples::F16(values.map(f16::from_f32).collect())
+                    } else {
+                        FlatSamples::F32(values.collect())
+                    };
+                    AnyChannel::new(name, samples)
+                })
+                .collect();
+            Layer::new(
+                size,
+                LayerAttributes::named(aov.name()),
+                Encoding::FAST_LOSSLESS,
+                AnyChannels::sort(channels),
+            )
+        })
+        .collect();
+    let attributes = ImageAttributes::new(IntegerBounds::from_dimensions(size));
+    Image::from_layers(attributes, layers).write().to_file(path)
+}
+
+/// Undo the gamma the renderer works in
+fn linearize(n: f32) -> f32 {
+    n.max(0.0).powf(2.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_from_extension() {
+        let format = |path: &str| Format::from_path(Path::new(path));
+        assert_eq!(format("out.png"), Ok(Format::Png));
+        assert_eq!(format("out.PPM"), Ok(Format::Ppm));
+        assert_eq!(format("renders/out.tif"), Ok(Format::Tiff16));
+        assert_eq!(format("out.exr"), Ok(Format::ExrHalf));
+        assert!(format("out.jpg").is_err());
+        assert!(format("out").is_err());
+    }
+
+    #[test]
+    fn aov_paths() {
+        let path = Path::new("renders/out.png");
+        assert_eq!(aov_path(path, Aov::Beauty), path);
+        assert_eq!(
+            aov_path(path, Aov::Depth),
+            Path::new("renders/out.depth.png")
+        );
+        assert_eq!(
+            aov_path(Path::new("out"), Aov::Object),
+            Path::new("out.object")
+        );
+    }
+
+    #[test]
+    fn quantize_clamps() {
+        assert_eq!(u8::quantize(0.5), 128);
+        assert_eq!(u8::quantize(4.0), 255);
+        assert_eq!(u16::quantize(-1.0), 0);
+        assert_eq!(u16::quantize(1.0), 65535);
+    }
+}
@@ -0,0 +1,113 @@
+/// Size each pixel of a glyph is drawn at
+const SCALE: usize = 2;
+/// Glyphs are 3 by 5 pixels, with a pixel of space after each
+const ADVANCE: usize = 4 * SCALE;
+const LINE_HEIGHT: usize = 7 * SCALE;
+/// Space between the text and the edges of its panel
+const MARGIN: usize = 3 * SCALE;
+
+/// Draw `lines` of text in the top left corner of an RGBA `frame` that is `width` pixels wide,
+/// on a darkened panel so that it stays readable over any image. Lowercase letters are shown as
+/// uppercase ones, and text that does not fit is cut off.
+pub fn draw_text(frame: &mut [u8], width: usize, lines: &[String]) {
+    let height = frame.len() / 4 / width;
+    let columns = lines.iter().map(|line| line.chars().count()).max();
+    let panel_width = (2 * MARGIN + columns.unwrap_or(0) * ADVANCE).min(width);
+    let panel_height = (2 * MARGIN + lines.len() * LINE_HEIGHT).min(height);
+
+    for y in 0..panel_height {
+        for x in 0..panel_width {
+            let i = 4 * (y * width + x);
+            frame[i..i + 3].iter_mut().for_each(|n| *n /= 4);
+        }
+    }
+
+    for (row, line) in lines.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            let (left, top) = (MARGIN + column * ADVANCE, MARGIN + row * LINE_HEIGHT);
+            for (gy, bits) in glyph(c).iter().enumerate() {
+                for gx in 0..3 {
+                    if bits & (0b100 >> gx) == 0 {
+                        continue;
+                    }
+                    let (x, y) = (left + gx * SCALE, top + gy * SCALE);
+                    for y in y..(y + SCALE).min(panel_height) {
+                        for x in x..(x + SCALE).min(panel_width) {
+                            let i = 4 * (y * width + x);
+                            frame[i..i + 3].fill(0xff);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of a 3 by 5 pixel glyph for `c`, top to bottom with the leftmost pixel in the highest bit
+const fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        ' ' => [0; 5],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_stays_in_frame() {
+        let (width, height) = (200, 40);
+        let mut frame = vec![0x80; width * height * 4];
+        let lines = ["W: Move forward".to_owned(), "?".to_owned()];
+        draw_text(&mut frame, width, &lines);
+        // Darkened behind the text, lit where the glyphs are, and untouched past the panel
+        assert_eq!(frame[0], 0x20);
+        assert!(frame.chunks(4).any(|px| px[0] == 0xff));
+        assert_eq!(frame[4 * (width * height - 1)], 0x80);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::camera::RayCounts;
+
+use std::{
+    io::{IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+/// Width of the progress bar (in characters)
+const BAR_WIDTH: usize = 30;
+
+/// Reports how far along a headless render is on standard error: as a progress bar redrawn in
+/// place on a terminal, or as a log line every few seconds otherwise
+pub struct Progress {
+    /// What is being rendered, such as the name of an AOV
+    label: String,
+    start: Instant,
+    last_report: Instant,
+    total_tiles: usize,
+    finished_tiles: usize,
+    rays: RayCounts,
+    terminal: bool,
+}
+
+impl Progress {
+    pub fn new(label: impl Into<String>, total_tiles: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            label: label.into(),
+            start: now,
+            last_report: now,
+            total_tiles,
+            finished_tiles: 0,
+            rays: RayCounts::default(),
+            terminal: std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// Count a finished tile along with the rays traced for it
+    pub fn tile_done(&mut self, rays: RayCounts) {
+        self.finished_tiles += 1;
+        self.rays += rays;
+
+        // Redrawing a bar is cheap, but log lines should not flood the output
+        let interval = if self.terminal {
+            Duration::from_millis(100)
+        } else {
+            Duration::from_secs(5)
+        };
+        if self.last_report.elapsed() < interval && self.finished_tiles < self.total_tiles {
+            return;
+        }
+        self.last_report = Instant::now();
+
+        let elapsed = self.start.elapsed();
+        let fraction = self.finished_tiles as f64 / self.total_tiles as f64;
+        let remaining = elapsed.as_secs_f64() * (1.0 - fraction) / fraction;
+        let status = format!(
+            "{}/{} tiles, {} rays/s, {} elapsed, {} left",
+            self.finished_tiles,
+            self.total_tiles,
+            format_count(rays_per_sec(self.rays.total(), elapsed)),
+            format_duration(elapsed.as_secs_f64()),
+            format_duration(remaining),
+        );
+        if self.terminal {
+            #[allow(clippy::cast_possible_truncation)]
+            let filled = (fraction * BAR_WIDTH as f64) as usize;
+            let bar = format!("{:<BAR_WIDTH$}", "=".repeat(filled));
+            eprint!("\r{} [{bar}] {status}", self.label);
+            // The line is only ended by the summary, so it has to be flushed by hand
+            let _ = std::io::stderr().flush();
+        } else {
+            eprintln!("{}: {status}", self.label);
+        }
+    }
+
+    /// Print a summary of the render
+    pub fn finish(self) {
+        if self.terminal {
+            eprintln!();
+        }
+        let elapsed = self.start.elapsed();
+        eprintln!(
+            "Rendered {} in {}: {} primary, {} shadow and {} secondary rays ({} rays/s)",
+            self.label,
+            format_duration(elapsed.as_secs_f64()),
+            format_count(self.rays.primary),
+            format_count(self.rays.shadow),
+            format_count(self.rays.secondary),
+            format_count(rays_per_sec(self.rays.total(), elapsed)),
+        );
+    }
+}
+
+/// Rays traced per second over `elapsed`, which is taken to be at least a millisecond
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn rays_per_sec(rays: u64, elapsed: Duration) -> u64 {
+    (rays as f64 / elapsed.as_secs_f64().max(1e-3)) as u64
+}
+
+/// Count abbreviated with a metric suffix, like `12.3M`
+fn format_count(n: u64) -> String {
+    let n = n as f64;
+    if n >= 1e9 {
+        format!("{:.1}G", n / 1e9)
+    } else if n >= 1e6 {
+        format!("{:.1}M", n / 1e6)
+    } else if n >= 1e3 {
+        format!("{:.1}k", n / 1e3)
+    } else {
+        format!("{n}")
+    }
+}
+
+/// Duration in seconds as minutes and seconds, like `2m05s`
+#[allow(clippy::cast_possible_truncation)]
+fn format_duration(secs: f64) -> String {
+    if !secs.is_finite() {
+        return "?".to_owned();
+    }
+    let secs = secs.round() as u64;
+    if secs < 60 {
+        format!("{secs}s")
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts() {
+        assert_eq!(format_count(950), "950");
+        assert_eq!(format_count(12_345), "12.3k");
+        assert_eq!(format_count(4_200_000), "4.2M");
+        assert_eq!(format_count(7_000_000_000), "7.0G");
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(4.4), "4s");
+        assert_eq!(format_duration(125.0), "2m05s");
+        assert_eq!(format_duration(f64::INFINITY), "?");
+    }
+}
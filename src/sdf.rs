@@ -0,0 +1,165 @@
+use crate::{bvh::Aabb, math::Vec3};
+
+use serde::Deserialize;
+
+/// Signed distance field expression tree.
+///
+/// Leaves are primitives centered on the origin, inner nodes transform the space they are evaluated
+/// in or combine the distances of their children.
+#[derive(Deserialize)]
+pub enum Sdf {
+    /// Sphere (Radius)
+    Sphere(f32),
+    /// Axis aligned box (Half extents)
+    Box(Vec3),
+    /// Axis aligned box with rounded edges (Half extents, Rounding radius)
+    RoundBox(Vec3, f32),
+    /// Torus lying in the xy plane (Major radius, Minor radius)
+    Torus(f32, f32),
+    /// Moves a shape (Offset, Shape)
+    Translate(Vec3, Box<Sdf>),
+    /// Union of two shapes (Shape 1, Shape 2)
+    Union(Box<Sdf>, Box<Sdf>),
+    /// Union of two shapes with a blended seam (Shape 1, Shape 2, Blend radius)
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f32),
+    /// Intersection of two shapes (Shape 1, Shape 2)
+    Intersection(Box<Sdf>, Box<Sdf>),
+    /// First shape with the second one carved out of it (Shape 1, Shape 2)
+    Difference(Box<Sdf>, Box<Sdf>),
+    /// Infinite repetition of a shape (Period along each axis, 0 to not repeat; Shape)
+    Repeat(Vec3, Box<Sdf>),
+    /// Twist around the z axis (Radians per unit of height, Shape)
+    Twist(f32, Box<Sdf>),
+}
+
+impl Sdf {
+    /// Distance from `p` to the surface, negative inside the shape.
+    pub fn distance(&self, p: Vec3) -> f32 {
+        match self {
+            Self::Sphere(r) => p.mag() - r,
+            Self::Box(half) => {
+                let q = p.abs() - *half;
+                q.max(Vec3::default()).mag() + q.max_elem().min(0.0)
+            }
+            Self::RoundBox(half, r) => {
+                let q = p.abs() - *half + Vec3::new(*r, *r, *r);
+                q.max(Vec3::default()).mag() + q.max_elem().min(0.0) - r
+            }
+            Self::Torus(major, minor) => {
+                let ring = p.x.hypot(p.y) - major;
+                ring.hypot(p.z) - minor
+            }
+            Self::Translate(offset, sdf) => sdf.distance(p - *offset),
+            Self::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Self::SmoothUnion(a, b, blend) => {
+                let (da, db) = (a.distance(p), b.distance(p));
+                // Without a seam to blend, it is a plain union
+                if *blend <= 0.0 {
+                    return da.min(db);
+                }
+                let h = (blend - (da - db).abs()).max(0.0) / blend;
+                da.min(db) - h * h * blend / 4.0
+            }
+            Self::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Self::Difference(a, b) => a.distance(p).max(-b.distance(p)),
+            Self::Repeat(period, sdf) => {
+                let wrap = |n: f32, period: f32| {
+                    if period > 0.0 {
+                        n - period * (n / period).round()
+                    } else {
+                        n
+                    }
+                };
+                sdf.distance(Vec3::new(
+                    wrap(p.x, period.x),
+                    wrap(p.y, period.y),
+                    wrap(p.z, period.z),
+                ))
+            }
+            Self::Twist(rate, sdf) => {
+                let (sin, cos) = (rate * p.z).sin_cos();
+                let q = Vec3::new(cos * p.x - sin * p.y, sin * p.x + cos * p.y, p.z);
+                // Twisting stretches space, so the inner distance overestimates the real one.
+                // Dividing by the local stretch factor keeps the sphere tracing from overshooting.
+                let stretch = (rate * p.x.hypot(p.y)).hypot(1.0);
+                sdf.distance(q) / stretch
+            }
+        }
+    }
+
+    /// Conservative bounds of the shape, infinite along the axes it repeats on
+    pub fn bounds(&self) -> Aabb {
+        let centered = |half: Vec3| Aabb {
+            min: -half,
+            max: half,
+        };
+        match self {
+            Self::Sphere(r) => centered(Vec3::new(*r, *r, *r)),
+            Self::Box(half) | Self::RoundBox(half, _) => centered(*half),
+            Self::Torus(major, minor) => centered(Vec3::new(major + minor, major + minor, *minor)),
+            Self::Translate(offset, sdf) => {
+                let Aabb { min, max } = sdf.bounds();
+                Aabb {
+                    min: min + *offset,
+                    max: max + *offset,
+                }
+            }
+            Self::Union(a, b) => a.bounds().union(b.bounds()),
+            Self::SmoothUnion(a, b, blend) => {
+                // The blend can only bulge out by a quarter of its radius
+                let Aabb { min, max } = a.bounds().union(b.bounds());
+                let bulge = Vec3::new(*blend, *blend, *blend) / 4.0;
+                Aabb {
+                    min: min - bulge,
+                    max: max + bulge,
+                }
+            }
+            Self::Intersection(a, b) => {
+                let (a, b) = (a.bounds(), b.bounds());
+                Aabb {
+                    min: a.min.max(b.min),
+                    max: a.max.min(b.max),
+                }
+            }
+            Self::Difference(a, _) => a.bounds(),
+            Self::Repeat(period, sdf) => {
+                let Aabb { mut min, mut max } = sdf.bounds();
+                for (min, max, period) in [
+                    (&mut min.x, &mut max.x, period.x),
+                    (&mut min.y, &mut max.y, period.y),
+                    (&mut min.z, &mut max.z, period.z),
+                ] {
+                    if period > 0.0 {
+                        (*min, *max) = (f32::NEG_INFINITY, f32::INFINITY);
+                    }
+                }
+                Aabb { min, max }
+            }
+            Self::Twist(_, sdf) => {
+                // Twisting sweeps the shape around the z axis
+                let Aabb { min, max } = sdf.bounds();
+                let corner = min.abs().max(max.abs());
+                let r = corner.x.hypot(corner.y);
+                Aabb {
+                    min: Vec3::new(-r, -r, min.z),
+                    max: Vec3::new(r, r, max.z),
+                }
+            }
+        }
+    }
+
+    /// Surface normal at `p`, estimated from the gradient of the field.
+    pub fn normal(&self, p: Vec3) -> Vec3 {
+        // Tetrahedral central differences need 4 evaluations instead of 6.
+        const H: f32 = 1e-4;
+        [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ]
+        .into_iter()
+        .fold(Vec3::default(), |acc, k| acc + k * self.distance(p + k * H))
+        .normalize()
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    math::{fbm, turbulence, Vec3},
+    world::Color,
+};
+
+use serde::Deserialize;
+
+/// How texture coordinates outside of the `0.0..=1.0` range are handled
+#[derive(Clone, Copy, Default, Deserialize)]
+pub enum Wrap {
+    /// Tile the image
+    #[default]
+    Repeat,
+    /// Extend the edge texels
+    Clamp,
+}
+
+/// Image sampled with bilinear filtering
+pub struct Texture {
+    width: u32,
+    height: u32,
+    texels: Vec<Color>,
+    wrap: Wrap,
+}
+
+/// Scene file form of a [`Texture`]
+#[derive(Deserialize)]
+#[serde(rename = "Texture")]
+pub struct TextureDef {
+    /// Path of a PNG or PPM image, relative to the working directory
+    pub path: String,
+    #[serde(default)]
+    pub wrap: Wrap,
+}
+
+impl TryFrom<TextureDef> for Texture {
+    type Error = String;
+
+    fn try_from(def: TextureDef) -> Result<Self, String> {
+        let image = image::open(&def.path)
+            .map_err(|err| format!("failed to load texture `{}`: {err}", def.path))?
+            .to_rgb8();
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            texels: image.pixels().map(|px| Color(px.0)).collect(),
+            wrap: def.wrap,
+        })
+    }
+}
+
+impl Texture {
+    /// Color at texture coordinates `(u, v)`, where `(0.0, 0.0)` is the top left corner of the
+    /// image and `(1.0, 1.0)` the bottom right one
+    pub fn sample(&self, uv: (f32, f32)) -> Color {
+        bilinear(
+            uv,
+            (self.width, self.height),
+            |x, y| self.texel(x, y),
+            Color::interpolate,
+        )
+    }
+
+    /// Tangent space normal stored at `uv`, with red along `u`, green against `v` (up in the
+    /// image) and blue out of the surface
+    pub fn normal_at(&self, uv: (f32, f32)) -> Vec3 {
+        let Color([r, g, b]) = self.sample(uv);
+        let decode = |n: u8| f32::from(n) / 127.5 - 1.0;
+        Vec3::new(decode(r), decode(g), decode(b))
+    }
+
+    /// Change in brightness of the image over one texel along `u` and `v` at `uv`, treating it as
+    /// a height map
+    pub fn slope_at(&self, (u, v): (f32, f32)) -> (f32, f32) {
+        let (du, dv) = (0.5 / self.width as f32, 0.5 / self.height as f32);
+        let height = |uv| self.sample(uv).luminance();
+        (
+            height((u + du, v)) - height((u - du, v)),
+            height((u, v + dv)) - height((u, v - dv)),
+        )
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let wrap = |n: i64, len: u32| match self.wrap {
+            Wrap::Repeat => n.rem_euclid(len as i64),
+            Wrap::Clamp => n.clamp(0, len as i64 - 1),
+        } as usize;
+        self.texels[wrap(y, self.height) * self.width as usize + wrap(x, self.width)]
+    }
+}
+
+/// Equirectangular high dynamic range image of the surroundings of the scene
+#[derive(Deserialize)]
+#[serde(try_from = "EnvironmentMapDef")]
+pub struct EnvironmentMap {
+    width: u32,
+    height: u32,
+    /// Linear radiance
+    texels: Vec<[f32; 3]>,
+    exposure: f32,
+}
+
+/// Scene file form of an [`EnvironmentMap`]
+#[derive(Deserialize)]
+#[serde(rename = "EnvironmentMap")]
+struct EnvironmentMapDef {
+    /// Path of a Radiance HDR image, relative to the working directory
+    path: String,
+    /// Factor the radiance is scaled by
+    #[serde(default = "EnvironmentMapDef::default_exposure")]
+    exposure: f32,
+}
+
+impl EnvironmentMapDef {
+    const fn default_exposure() -> f32 {
+        1.0
+    }
+}
+
+impl TryFrom<EnvironmentMapDef> for EnvironmentMap {
+    type Error = String;
+
+    fn try_from(def: EnvironmentMapDef) -> Result<Self, String> {
+        let image = image::open(&def.path)
+            .map_err(|err| format!("failed to load environment map `{}`: {err}", def.path))?
+            .into_rgb32f();
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            texels: image.pixels().map(|px| px.0).collect(),
+            exposure: def.exposure,
+        })
+    }
+}
+
+impl EnvironmentMap {
+    /// Color seen looking along `dir`, with channels brighter than white kept above `1.0`. The z
+    /// axis points to the top of the image and the horizontal center of the image is along +x.
+    pub fn sample(&self, dir: Vec3) -> [f32; 3] {
+        let dir = dir.normalize();
+        let u = 0.5 + dir.y.atan2(dir.x) * std::f32::consts::FRAC_1_PI / 2.0;
+        let v = dir.z.clamp(-1.0, 1.0).acos() * std::f32::consts::FRAC_1_PI;
+
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+        let radiance = bilinear(
+            (u, v),
+            (self.width, self.height),
+            |x, y| self.texel(x, y),
+            lerp,
+        );
+
+        // The rest of the renderer works with gamma encoded colors
+        radiance.map(|n| (n * self.exposure).powf(1.0 / 2.2))
+    }
+
+    /// Texel wrapping around horizontally and clamped vertically
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn texel(&self, x: i64, y: i64) -> [f32; 3] {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        self.texels[y * self.width as usize + x]
+    }
+}
+
+/// Blend of the four texels around texture coordinates `(u, v)` of a `width` by `height` image,
+/// which are looked up by `texel` from indices that may be out of range and mixed by `lerp`
+fn bilinear<T: Copy>(
+    (u, v): (f32, f32),
+    (width, height): (u32, u32),
+    texel: impl Fn(i64, i64) -> T,
+    lerp: impl Fn(T, T, f32) -> T,
+) -> T {
+    // Texel centers are at half coordinates
+    let x = u * width as f32 - 0.5;
+    let y = v * height as f32 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+
+    #[allow(clippy::cast_possible_truncation)]
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let top = lerp(texel(x0, y0), texel(x0 + 1, y0), tx);
+    let bottom = lerp(texel(x0, y0 + 1), texel(x0 + 1, y0 + 1), tx);
+    lerp(top, bottom, ty)
+}
+
+/// Procedural color pattern, evaluated at a point in space
+#[derive(Clone, Deserialize)]
+pub enum Pattern {
+    /// Alternating cubic cells (Cell size, Color 1, Color 2)
+    Checker(f32, Color, Color),
+    /// Slabs alternating along the x axis (Stripe width, Color 1, Color 2)
+    Stripes(f32, Color, Color),
+    /// Fractal gradient noise (Feature size, Octaves, Color 1, Color 2)
+    Noise(f32, u32, Color, Color),
+    /// Veins along the x axis, distorted by turbulence (Vein spacing, Turbulence, Color 1, Color 2)
+    Marble(f32, f32, Color, Color),
+    /// Growth rings around the z axis, distorted by noise (Ring spacing, Distortion, Color 1, Color 2)
+    Wood(f32, f32, Color, Color),
+}
+
+/// Space in which a [`Pattern`] is evaluated
+#[derive(Clone, Copy, Default, Deserialize)]
+pub enum Space {
+    World,
+    /// Local space of the object, so that the pattern moves along with instances
+    #[default]
+    Object,
+}
+
+impl Pattern {
+    pub fn color_at(&self, p: Vec3) -> Color {
+        /// Octaves of the noise distorting marble and wood
+        const OCTAVES: u32 = 5;
+        let parity = |n: f32| n.floor().rem_euclid(2.0) == 0.0;
+        match *self {
+            Self::Checker(size, c1, c2) => {
+                let cell = p / size;
+                let even = parity(cell.x) ^ parity(cell.y) ^ parity(cell.z);
+                if even {
+                    c1
+                } else {
+                    c2
+                }
+            }
+            Self::Stripes(width, c1, c2) => {
+                if parity(p.x / width) {
+                    c1
+                } else {
+                    c2
+                }
+            }
+            Self::Noise(size, octaves, c1, c2) => {
+                let n = fbm(p / size, octaves);
+                c1.interpolate(c2, (n * 0.5 + 0.5).clamp(0.0, 1.0))
+            }
+            Self::Marble(spacing, turb, c1, c2) => {
+                let phase = p.x / spacing + turb * turbulence(p / spacing, OCTAVES);
+                let vein = (phase * std::f32::consts::PI).sin() * 0.5 + 0.5;
+                c1.interpolate(c2, vein)
+            }
+            Self::Wood(spacing, distortion, c1, c2) => {
+                let rings = p.x.hypot(p.y) / spacing + distortion * fbm(p / spacing, OCTAVES);
+                c1.interpolate(c2, rings.rem_euclid(1.0))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2x1 texture, black on the left and white on the right
+    fn texture(wrap: Wrap) -> Texture {
+        Texture {
+            width: 2,
+            height: 1,
+            texels: vec![Color::BLACK, Color([255; 3])],
+            wrap,
+        }
+    }
+
+    #[test]
+    fn bilinear() {
+        let texture = texture(Wrap::Clamp);
+        assert_eq!(texture.sample((0.25, 0.5)).0, [0; 3]);
+        assert_eq!(texture.sample((0.5, 0.5)).0, [128; 3]);
+        assert_eq!(texture.sample((0.75, 0.5)).0, [255; 3]);
+    }
+
+    #[test]
+    fn wrap_modes() {
+        // Past the right edge, clamping stays white while repeating blends back into black
+        assert_eq!(texture(Wrap::Clamp).sample((1.0, 0.5)).0, [255; 3]);
+        assert_eq!(texture(Wrap::Repeat).sample((1.0, 0.5)).0, [128; 3]);
+        assert_eq!(texture(Wrap::Repeat).sample((1.25, 0.5)).0, [0; 3]);
+    }
+
+    #[test]
+    fn slope() {
+        let texture = texture(Wrap::Clamp);
+        let (du, dv) = texture.slope_at((0.5, 0.5));
+        assert!((du - 1.0).abs() < 0.01);
+        assert!(dv.abs() < 1e-6);
+    }
+
+    #[test]
+    fn flat_normal() {
+        let texture = Texture {
+            width: 1,
+            height: 1,
+            texels: vec![Color([128, 128, 255])],
+            wrap: Wrap::Repeat,
+        };
+        let normal = texture.normal_at((0.5, 0.5));
+        assert!((normal - Vec3::new(0.0, 0.0, 1.0)).mag() < 0.01);
+    }
+
+    #[test]
+    fn checker_cells() {
+        let (c1, c2) = (Color::BLACK, Color::WHITE);
+        let checker = Pattern::Checker(1.0, c1, c2);
+        assert_eq!(checker.color_at(Vec3::new(0.5, 0.5, 0.5)).0, c1.0);
+        assert_eq!(checker.color_at(Vec3::new(1.5, 0.5, 0.5)).0, c2.0);
+        assert_eq!(checker.color_at(Vec3::new(-0.5, 0.5, 0.5)).0, c2.0);
+        assert_eq!(checker.color_at(Vec3::new(1.5, 1.5, 0.5)).0, c1.0);
+    }
+}
@@ -0,0 +1,215 @@
+use rayon::prelude::*;
+use std::{str::FromStr, sync::mpsc};
+
+/// Rectangle of pixels rendered as a unit, which keeps the rays of a thread close together
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    /// Coordinates of the pixels of the tile, row by row
+    pub fn pixels(self) -> impl Iterator<Item = (u32, u32)> {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+/// Order tiles are rendered in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    /// Outwards from the center of the frame, where the subject usually is
+    #[default]
+    Spiral,
+    /// Along a Hilbert curve, so that consecutive tiles are next to each other
+    Hilbert,
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        match name {
+            "spiral" => Ok(Self::Spiral),
+            "hilbert" => Ok(Self::Hilbert),
+            _ => Err(format!(
+                "unknown tile order `{name}`, expected spiral or hilbert"
+            )),
+        }
+    }
+}
+
+/// How a frame is split into tiles
+#[derive(Clone, Copy, Debug)]
+pub struct Tiling {
+    /// Width and height of the tiles (in pixels, not zero), smaller along the right and bottom
+    /// edges
+    pub size: u32,
+    pub order: Order,
+}
+
+impl Default for Tiling {
+    fn default() -> Self {
+        Self {
+            size: 32,
+            order: Order::default(),
+        }
+    }
+}
+
+impl Tiling {
+    /// Tiles covering a frame of `dims`, in the order they should be rendered
+    pub fn tiles(self, dims: (u32, u32)) -> Vec<Tile> {
+        let size = self.size;
+        let (cols, rows) = (dims.0.div_ceil(size), dims.1.div_ceil(size));
+        let mut cells: Vec<(u32, u32)> = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (col, row)))
+            .collect();
+        match self.order {
+            Order::Spiral => {
+                let center = (cols as f32 / 2.0 - 0.5, rows as f32 / 2.0 - 0.5);
+                let key = |&(col, row): &(u32, u32)| {
+                    let (dx, dy) = (col as f32 - center.0, row as f32 - center.1);
+                    (dx.abs().max(dy.abs()).round(), dy.atan2(dx))
+                };
+                cells.sort_by(|a, b| {
+                    let (a, b) = (key(a), key(b));
+                    a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+                });
+            }
+            Order::Hilbert => {
+                let grid = cols.max(rows).next_power_of_two();
+                cells.sort_by_key(|&(col, row)| hilbert_index(grid, col, row));
+            }
+        }
+        cells
+            .into_iter()
+            .map(|(col, row)| {
+                let (x, y) = (col * size, row * size);
+                Tile {
+                    x,
+                    y,
+                    width: size.min(dims.0 - x),
+                    height: size.min(dims.1 - y),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Distance along the Hilbert curve filling a `side` by `side` grid, `side` being a power of two,
+/// to the cell at `(x, y)`
+fn hilbert_index(side: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = u32::from(x & s > 0);
+        let ry = u32::from(y & s > 0);
+        index += u64::from(s) * u64::from(s) * u64::from((3 * rx) ^ ry);
+        // Turn the quadrant so that the curve through it lines up with its neighbours
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/// Compute `pixel` for every pixel of `tiles` in parallel, starting the tiles in order. Each tile
+/// is handed to `done` on the calling thread once it completes, along with its values row by
+/// row, so that it can be shown or saved while the others are still rendering.
+pub fn render<T: Send>(
+    tiles: Vec<Tile>,
+    pixel: impl Fn(u32, u32) -> T + Sync,
+    mut done: impl FnMut(Tile, Vec<T>),
+) {
+    let (sender, receiver) = mpsc::channel();
+    let pixel = &pixel;
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            // Bridging takes the tiles in order, where splitting the list would start in the middle
+            tiles
+                .into_iter()
+                .par_bridge()
+                .for_each_with(sender, |sender, tile| {
+                    let values = tile.pixels().map(|(x, y)| pixel(x, y)).collect();
+                    // Only fails if the receiving end panicked, which the scope reports anyway
+                    let _ = sender.send((tile, values));
+                });
+        });
+        for (tile, values) in receiver {
+            done(tile, values);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_frame() {
+        const DIMS: (u32, u32) = (70, 45);
+        for order in [Order::Spiral, Order::Hilbert] {
+            let tiling = Tiling { size: 16, order };
+            let mut covered = vec![0; (DIMS.0 * DIMS.1) as usize];
+            for tile in tiling.tiles(DIMS) {
+                for (x, y) in tile.pixels() {
+                    covered[(y * DIMS.0 + x) as usize] += 1;
+                }
+            }
+            assert!(covered.iter().all(|&n| n == 1));
+        }
+    }
+
+    #[test]
+    fn spiral_starts_at_center() {
+        let tiling = Tiling {
+            size: 10,
+            order: Order::Spiral,
+        };
+        let tiles = tiling.tiles((50, 30));
+        assert_eq!((tiles[0].x, tiles[0].y), (20, 10));
+    }
+
+    #[test]
+    fn hilbert_tiles_are_adjacent() {
+        let tiling = Tiling {
+            size: 8,
+            order: Order::Hilbert,
+        };
+        let tiles = tiling.tiles((64, 64));
+        for pair in tiles.windows(2) {
+            let dist = pair[0].x.abs_diff(pair[1].x) + pair[0].y.abs_diff(pair[1].y);
+            assert_eq!(dist, 8);
+        }
+    }
+
+    #[test]
+    fn render_hands_over_every_tile() {
+        let tiles = Tiling::default().tiles((100, 40));
+        let count = tiles.len();
+        let mut sum = 0;
+        let mut received = 0;
+        render(
+            tiles,
+            |x, y| x + y,
+            |tile, values| {
+                assert_eq!(values.len(), (tile.width * tile.height) as usize);
+                assert_eq!(values[0], tile.x + tile.y);
+                sum += values.iter().sum::<u32>();
+                received += 1;
+            },
+        );
+        assert_eq!(received, count);
+        // Every `x` appears 40 times, every `y` 100 times
+        assert_eq!(sum, 40 * (0..100).sum::<u32>() + 100 * (0..40).sum::<u32>());
+    }
+}
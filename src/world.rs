@@ -1,34 +1,776 @@
-use crate::math::{Quat, Vec3};
+use crate::{
+    brdf::Pbr,
+    bvh::{Aabb, Bvh},
+    math::{Quat, Vec3},
+    mesh::TriangleMesh,
+    sdf::Sdf,
+    texture::{EnvironmentMap, Pattern, Space, Texture, TextureDef},
+};
 
+use ron::extensions::Extensions;
 use serde::Deserialize;
+use std::{collections::HashMap, fmt, sync::Arc};
 
 #[derive(Default, Deserialize)]
 pub struct World {
     pub objects: Vec<Object>,
-    pub light: Vec3,
+    /// Point light, the scene is only lit by emissive objects without it
+    #[serde(default)]
+    pub light: Option<Vec3>,
+    /// Crease angle (in degrees) below which triangles sharing a vertex are shaded smoothly.
+    /// Triangles are left flat if unset.
+    #[serde(default)]
+    pub smooth_normals: Option<f32>,
+    /// Named objects, placed in the world by `instances` and `nodes`
+    #[serde(default)]
+    pub geometry: HashMap<String, Arc<Object>>,
+    #[serde(default)]
+    pub instances: Vec<InstanceDef>,
+    /// Roots of the scene graph
+    #[serde(default)]
+    pub nodes: Vec<Node>,
+    /// What rays that miss every object see
+    #[serde(default)]
+    pub background: Background,
+    /// Fog or medium filling the space between the camera and what it sees
+    #[serde(default)]
+    pub atmosphere: Option<Atmosphere>,
+    /// Samples taken on every emissive object when lighting a point
+    #[serde(default = "World::default_area_light_samples")]
+    pub area_light_samples: u32,
+    /// Reflection rays traced from physically based materials seen by the camera
+    #[serde(default = "World::default_glossy_samples")]
+    pub glossy_samples: u32,
+    /// Extra distance that shadow, reflection and refraction rays start off the surface they
+    /// leave from, for scenes where the offset scaled to their coordinates is not enough
+    #[serde(default)]
+    pub ray_bias: f32,
+    /// Emissive surfaces in world space, filled in by `World::load`
+    #[serde(skip)]
+    pub area_lights: Vec<AreaLight>,
+    /// Every instance and object of the scene graph placed in world space, filled in by
+    /// `World::load`
+    #[serde(skip)]
+    pub placed: Vec<Instance>,
+    /// Hierarchy over the world space bounds of `placed`
+    #[serde(skip)]
+    pub instance_bvh: Bvh,
+}
+
+/// Color of the surroundings of the scene, seen by rays that hit nothing
+#[derive(Deserialize)]
+pub enum Background {
+    /// Same color in every direction
+    Solid(Color),
+    /// Sky blending upwards from the horizon, which also continues below it (Horizon color,
+    /// Zenith color)
+    Gradient(Color, Color),
+    /// Image of the surroundings
+    Environment(EnvironmentMap),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::Solid(Color::BLACK)
+    }
+}
+
+impl Background {
+    /// Color seen looking along `dir`, which can be brighter than white for environment maps
+    pub fn radiance(&self, dir: Vec3) -> [f32; 3] {
+        match self {
+            Self::Solid(color) => color.to_rgb(),
+            Self::Gradient(horizon, zenith) => {
+                let height = dir.z / dir.mag();
+                horizon.interpolate(*zenith, height.max(0.0)).to_rgb()
+            }
+            Self::Environment(map) => map.sample(dir),
+        }
+    }
+}
+
+/// Participating medium filling the whole world
+#[derive(Deserialize)]
+pub enum Atmosphere {
+    /// Exponential distance fog (Fog color, Density per unit of distance)
+    Fog(Color, f32),
+    /// Homogeneous medium scattering the light towards the camera, showing light shafts where
+    /// objects cast shadows into it
+    Medium {
+        /// Color of the light scattered by the medium
+        light_color: Color,
+        /// Share of the light scattered per unit of distance
+        scattering: f32,
+        /// Share of the light absorbed per unit of distance
+        absorption: f32,
+        /// Samples taken along each camera ray
+        #[serde(default = "Atmosphere::default_steps")]
+        steps: u32,
+        /// Distance from the camera past which the medium is not sampled anymore
+        #[serde(default = "Atmosphere::default_max_dist")]
+        max_dist: f32,
+    },
+}
+
+impl Atmosphere {
+    const fn default_steps() -> u32 {
+        64
+    }
+
+    const fn default_max_dist() -> f32 {
+        10.0
+    }
+}
+
+/// Emissive surface in world space, sampled to light other objects
+pub enum AreaLight {
+    /// (Center, Radius, Emission color, Emission strength)
+    Sphere(Vec3, f32, Color, f32),
+    /// (Points, Emission color, Emission strength)
+    Triangle([Vec3; 3], Color, f32),
+}
+
+impl AreaLight {
+    /// Pick a point on the light to light `target` with, from two uniform random numbers.
+    /// Returns the point, the normal of the light there, and the area it was picked from.
+    pub fn sample(&self, target: Vec3, (r1, r2): (f32, f32)) -> (Vec3, Vec3, f32) {
+        match *self {
+            Self::Sphere(center, r, ..) => {
+                // Only the half of the sphere facing the target can light it
+                let axis = (target - center).normalize();
+                let (side, up) = axis.orthonormal_basis();
+
+                let height = r1;
+                let ring = (1.0 - height * height).max(0.0).sqrt();
+                let (sin, cos) = (std::f32::consts::TAU * r2).sin_cos();
+                let normal = axis * height + side * (ring * cos) + up * (ring * sin);
+                (
+                    center + normal * r,
+                    normal,
+                    2.0 * std::f32::consts::PI * r * r,
+                )
+            }
+            Self::Triangle([p1, p2, p3], ..) => {
+                let sqrt = r1.sqrt();
+                let (u, v) = (sqrt * (1.0 - r2), sqrt * r2);
+                let cross = (p2 - p1).cross(p3 - p1);
+                (
+                    p1 + (p2 - p1) * u + (p3 - p1) * v,
+                    cross.normalize(),
+                    cross.mag() / 2.0,
+                )
+            }
+        }
+    }
+
+    /// Emission color and strength
+    pub const fn emission(&self) -> (Color, f32) {
+        match *self {
+            Self::Sphere(_, _, color, strength) | Self::Triangle(_, color, strength) => {
+                (color, strength)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Parse(ron::error::SpannedError),
+    /// An instance refers to geometry that does not exist (Name)
+    UnknownGeometry(String),
+    /// A node or instance has a transform that cannot be inverted, as its scale is not positive
+    /// or its rotation is zero (What is wrong)
+    DegenerateTransform(&'static str),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "{err}"),
+            Self::UnknownGeometry(name) => write!(f, "instance of unknown geometry `{name}`"),
+            Self::DegenerateTransform(what) => write!(f, "transform with {what}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl World {
+    /// Parse a RON world description and prepare it for rendering
+    pub fn load(src: &str) -> Result<Self, LoadError> {
+        // Lets optional fields like `light` be given without wrapping them in `Some`
+        let options = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+        let mut world = options.from_str::<Self>(src).map_err(LoadError::Parse)?;
+        if let Some(crease_angle) = world.smooth_normals {
+            world.generate_smooth_normals(crease_angle.to_radians());
+        }
+        world.place_instances()?;
+        world.collect_area_lights();
+        Ok(world)
+    }
+
+    /// Box around every bounded object and instance of the world, leaving out those that go on
+    /// forever like planes. Empty if nothing is bounded.
+    pub fn bounds(&self) -> Aabb {
+        let placed = self
+            .placed
+            .iter()
+            .map(|instance| instance.transform.bounds_to_world(instance.object.bounds()));
+        self.objects
+            .iter()
+            .map(Object::bounds)
+            .chain(placed)
+            .filter(|bounds| bounds.is_finite())
+            .fold(Aabb::EMPTY, Aabb::union)
+    }
+
+    const fn default_area_light_samples() -> u32 {
+        4
+    }
+
+    const fn default_glossy_samples() -> u32 {
+        4
+    }
+
+    /// Gather the emissive spheres and triangles of the world, including those of meshes and
+    /// placed instances. Emissive signed distance fields glow but do not light other objects.
+    fn collect_area_lights(&mut self) {
+        let mut lights = Vec::new();
+        let mut add = |object: &Object, transform: &Transform, material: &Material| {
+            if !material.is_emissive() {
+                return;
+            }
+            let (emission, strength) = (material.emission, material.emission_strength);
+            let mut add_triangle = |points: [Vec3; 3]| {
+                lights.push(AreaLight::Triangle(
+                    points.map(|p| transform.point_to_world(p)),
+                    emission,
+                    strength,
+                ));
+            };
+            match *object {
+                Object::Sphere(center, r, _) => lights.push(AreaLight::Sphere(
+                    transform.point_to_world(center),
+                    r * transform.scale,
+                    emission,
+                    strength,
+                )),
+                Object::Triangle(p1, p2, p3, _) | Object::SmoothTriangle(p1, p2, p3, _, _) => {
+                    add_triangle([p1, p2, p3]);
+                }
+                Object::Mesh(ref mesh) => {
+                    (0..mesh.indices.len()).for_each(|i| add_triangle(mesh.triangle(i)));
+                }
+                Object::Sdf(..) => {}
+            }
+        };
+
+        for object in &self.objects {
+            add(object, &Transform::default(), object.get_material());
+        }
+        for instance in &self.placed {
+            let material = instance
+                .material_override
+                .as_ref()
+                .unwrap_or_else(|| instance.object.get_material());
+            add(&instance.object, &instance.transform, material);
+        }
+        self.area_lights = lights;
+    }
+
+    /// Place every instance and scene graph node in world space and build the top level
+    /// hierarchy over them
+    fn place_instances(&mut self) -> Result<(), LoadError> {
+        let instances = std::mem::take(&mut self.instances)
+            .into_iter()
+            .map(Node::from);
+        let nodes: Vec<Node> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .chain(instances)
+            .collect();
+        for node in nodes {
+            self.place_node(node, &Transform::default(), None)?;
+        }
+
+        let bounds: Vec<Aabb> = self
+            .placed
+            .iter()
+            .map(|instance| instance.transform.bounds_to_world(instance.object.bounds()))
+            .collect();
+        self.instance_bvh = Bvh::build(&bounds);
+        Ok(())
+    }
+
+    /// Place the contents of `node` and its children, composing their transforms with that of
+    /// the `parent` and inheriting its material override
+    fn place_node(
+        &mut self,
+        node: Node,
+        parent: &Transform,
+        material_override: Option<&Material>,
+    ) -> Result<(), LoadError> {
+        // Rays are mapped into the local space by dividing by both
+        let Transform {
+            rotation, scale, ..
+        } = node.transform;
+        if scale <= 0.0 || !scale.is_finite() {
+            return Err(LoadError::DegenerateTransform("a non-positive scale"));
+        }
+        let magnitude = rotation.mag();
+        if magnitude == 0.0 || !magnitude.is_finite() {
+            return Err(LoadError::DegenerateTransform("a zero rotation"));
+        }
+        let transform = parent.compose(&node.transform.normalized());
+        let material_override = node
+            .material_override
+            .or_else(|| material_override.cloned());
+
+        let named = node
+            .geometry
+            .into_iter()
+            .map(|name| match self.geometry.get(&name) {
+                Some(object) => Ok(Arc::clone(object)),
+                None => Err(LoadError::UnknownGeometry(name)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for object in node.objects.into_iter().map(Arc::new).chain(named) {
+            self.placed.push(Instance {
+                object,
+                transform,
+                material_override: material_override.clone(),
+            });
+        }
+
+        for child in node.children {
+            self.place_node(child, &transform, material_override.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Turn every `Object::Triangle` into an `Object::SmoothTriangle`, averaging the normals of
+    /// the faces around each vertex that are within `crease_angle` (in radians) of the face.
+    fn generate_smooth_normals(&mut self, crease_angle: f32) {
+        // Vertices are matched by exact position. Adding 0.0 turns -0.0 into 0.0.
+        let key = |p: Vec3| [p.x, p.y, p.z].map(|n| (n + 0.0).to_bits());
+        let face_normal = |p1: Vec3, p2: Vec3, p3: Vec3| (p2 - p1).cross(p3 - p1);
+
+        // Unnormalized face normals, so that larger faces weigh more in the average
+        let mut adjacent: HashMap<[u32; 3], Vec<Vec3>> = HashMap::new();
+        for obj in &self.objects {
+            if let Object::Triangle(p1, p2, p3, _) = *obj {
+                let normal = face_normal(p1, p2, p3);
+                for p in [p1, p2, p3] {
+                    adjacent.entry(key(p)).or_default().push(normal);
+                }
+            }
+        }
+
+        let min_cos = crease_angle.cos();
+        for obj in &mut self.objects {
+            let Object::Triangle(p1, p2, p3, ref material) = *obj else {
+                continue;
+            };
+            let face = face_normal(p1, p2, p3);
+            // Degenerate triangles have no meaningful normal to smooth
+            if face.sq_mag() == 0.0 {
+                continue;
+            }
+            let face = face.normalize();
+            let normals = [p1, p2, p3].map(|p| {
+                adjacent[&key(p)]
+                    .iter()
+                    .filter(|n| n.normalize().dot(face) >= min_cos)
+                    .fold(Vec3::default(), |acc, &n| acc + n)
+                    .normalize()
+            });
+            *obj = Object::SmoothTriangle(p1, p2, p3, normals, material.clone());
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub enum Object {
-    /// Triangle Object (Point 1, Point 2, Point 3, Color)
-    Triangle(Vec3, Vec3, Vec3, Color),
-    /// Sphere object (Location, Radius, Color)
-    Sphere(Vec3, f32, Color),
+    /// Triangle Object (Point 1, Point 2, Point 3, Material)
+    Triangle(Vec3, Vec3, Vec3, Material),
+    /// Triangle with interpolated shading normals (Point 1, Point 2, Point 3, Vertex normals, Material)
+    SmoothTriangle(Vec3, Vec3, Vec3, [Vec3; 3], Material),
+    /// Sphere object (Location, Radius, Material)
+    Sphere(Vec3, f32, Material),
+    /// Signed distance field object, rendered by sphere tracing (Shape, Material)
+    Sdf(Sdf, Material),
+    /// Indexed triangle mesh
+    Mesh(TriangleMesh),
 }
 
 impl Object {
-    /// Fetch color of object
-    #[allow(dead_code)]
-    pub const fn get_color(&self) -> Color {
+    /// Bounds of the object, infinite if it has none
+    pub fn bounds(&self) -> Aabb {
         match self {
-            Self::Triangle(_, _, _, c) | Self::Sphere(_, _, c) => *c,
+            Self::Triangle(p1, p2, p3, _) | Self::SmoothTriangle(p1, p2, p3, _, _) => {
+                Aabb::from_points([*p1, *p2, *p3])
+            }
+            Self::Sphere(center, r, _) => Aabb {
+                min: *center - Vec3::new(*r, *r, *r),
+                max: *center + Vec3::new(*r, *r, *r),
+            },
+            Self::Sdf(sdf, _) => sdf.bounds(),
+            Self::Mesh(mesh) => mesh.bvh.bounds(),
+        }
+    }
+
+    /// Fetch material of object
+    pub const fn get_material(&self) -> &Material {
+        match self {
+            Self::Triangle(_, _, _, m)
+            | Self::SmoothTriangle(_, _, _, _, m)
+            | Self::Sphere(_, _, m)
+            | Self::Sdf(_, m)
+            | Self::Mesh(TriangleMesh { material: m, .. }) => m,
+        }
+    }
+}
+
+/// Surface properties of an object
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "MaterialDef")]
+pub struct Material {
+    /// Base color, multiplied with the texture and pattern if there are any
+    pub color: Color,
+    pub texture: Option<Arc<Texture>>,
+    pub pattern: Option<Pattern>,
+    pub pattern_space: Space,
+    /// Tangent space normal map, see [`Texture::normal_at`]
+    pub normal_map: Option<Arc<Texture>>,
+    /// Height map tilting the normal by its slope, ignored if there is a normal map
+    pub bump_map: Option<Arc<Texture>>,
+    /// Tilt of the normal per unit of brightness difference between neighboring texels of the
+    /// bump map
+    pub bump_strength: f32,
+    /// Color of the light given off by the surface, black for none
+    pub emission: Color,
+    /// Factor the emission is scaled by when lighting other objects. The surface itself is
+    /// seen with its emission clamped to displayable colors.
+    pub emission_strength: f32,
+    /// Physically based shading parameters, the surface is Lambertian without them
+    pub pbr: Option<Pbr>,
+    /// Share of the light let through the surface, in each channel. Black for opaque surfaces.
+    pub transmission: Color,
+    /// Index of refraction bending the rays seen through the surface
+    pub ior: f32,
+}
+
+/// Scene file form of a [`Material`]
+#[derive(Deserialize)]
+enum MaterialDef {
+    /// Shorthand for a plain material of the given color
+    Color([u8; 3]),
+    Material {
+        #[serde(default = "Color::white")]
+        color: Color,
+        #[serde(default)]
+        texture: Option<TextureDef>,
+        #[serde(default)]
+        pattern: Option<Pattern>,
+        #[serde(default)]
+        pattern_space: Space,
+        #[serde(default)]
+        normal_map: Option<TextureDef>,
+        #[serde(default)]
+        bump_map: Option<TextureDef>,
+        #[serde(default = "Material::default_bump_strength")]
+        bump_strength: f32,
+        #[serde(default = "Color::black")]
+        emission: Color,
+        #[serde(default = "Material::default_emission_strength")]
+        emission_strength: f32,
+        #[serde(default)]
+        pbr: Option<Pbr>,
+        #[serde(default = "Color::black")]
+        transmission: Color,
+        #[serde(default = "Material::default_ior")]
+        ior: f32,
+    },
+}
+
+impl TryFrom<MaterialDef> for Material {
+    type Error = String;
+
+    fn try_from(def: MaterialDef) -> Result<Self, String> {
+        Ok(match def {
+            MaterialDef::Color(color) => Self::plain(Color(color)),
+            MaterialDef::Material {
+                color,
+                texture,
+                pattern,
+                pattern_space,
+                normal_map,
+                bump_map,
+                bump_strength,
+                emission,
+                emission_strength,
+                pbr,
+                transmission,
+                ior,
+            } => {
+                let load = |def: Option<TextureDef>| {
+                    def.map(Texture::try_from)
+                        .transpose()
+                        .map(|t| t.map(Arc::new))
+                };
+                Self {
+                    color,
+                    texture: load(texture)?,
+                    pattern,
+                    pattern_space,
+                    normal_map: load(normal_map)?,
+                    bump_map: load(bump_map)?,
+                    bump_strength,
+                    emission,
+                    emission_strength,
+                    pbr,
+                    transmission,
+                    ior,
+                }
+            }
+        })
+    }
+}
+
+impl Material {
+    const fn default_bump_strength() -> f32 {
+        1.0
+    }
+
+    /// Untextured material of a single color
+    pub const fn plain(color: Color) -> Self {
+        Self {
+            color,
+            texture: None,
+            pattern: None,
+            pattern_space: Space::Object,
+            normal_map: None,
+            bump_map: None,
+            bump_strength: Self::default_bump_strength(),
+            emission: Color::BLACK,
+            emission_strength: Self::default_emission_strength(),
+            pbr: None,
+            transmission: Color::BLACK,
+            ior: Self::default_ior(),
+        }
+    }
+
+    const fn default_ior() -> f32 {
+        1.0
+    }
+
+    pub fn is_transmissive(&self) -> bool {
+        self.transmission.0 != [0; 3]
+    }
+
+    const fn default_emission_strength() -> f32 {
+        1.0
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission.0 != [0; 3] && self.emission_strength > 0.0
+    }
+
+    /// Emission scaled by its strength
+    pub fn emitted(&self) -> Color {
+        self.emission * self.emission_strength
+    }
+
+    /// Color of the surface at texture coordinates `uv`, for a point at `world_pos` in world
+    /// space and `object_pos` in the local space of its object
+    pub fn color_at(&self, uv: (f32, f32), world_pos: Vec3, object_pos: Vec3) -> Color {
+        let mut color = self.color;
+        if let Some(texture) = &self.texture {
+            color = color * texture.sample(uv);
+        }
+        if let Some(pattern) = &self.pattern {
+            let pos = match self.pattern_space {
+                Space::World => world_pos,
+                Space::Object => object_pos,
+            };
+            color = color * pattern.color_at(pos);
+        }
+        color
+    }
+
+    /// Shading normal at texture coordinates `uv`, tilted by the normal or bump map if there is
+    /// one. `tangents` are the directions in which `u` and `v` increase along the surface; the
+    /// normal is left as is where they are degenerate.
+    pub fn shading_normal(&self, uv: (f32, f32), normal: Vec3, tangents: (Vec3, Vec3)) -> Vec3 {
+        let local = if let Some(map) = &self.normal_map {
+            map.normal_at(uv)
+        } else if let Some(map) = &self.bump_map {
+            let (du, dv) = map.slope_at(uv);
+            // The surface faces away from the direction it rises in. `v` points down the image.
+            Vec3::new(-du * self.bump_strength, dv * self.bump_strength, 1.0)
+        } else {
+            return normal;
+        };
+
+        let (tangent, bitangent) = tangents;
+        let tangent = tangent - normal * normal.dot(tangent);
+        if tangent.sq_mag() < 1e-12 {
+            return normal;
+        }
+        let tangent = tangent.normalize();
+        // Up in the image, against `v`
+        let mut up = normal.cross(tangent);
+        if up.dot(bitangent) > 0.0 {
+            up = -up;
         }
+        (tangent * local.x + up * local.y + normal * local.z).normalize()
     }
 }
 
+/// Scene file placement of an object of `World::geometry`
+#[derive(Deserialize)]
+#[serde(rename = "Instance")]
+pub struct InstanceDef {
+    /// Name of the geometry
+    pub geometry: String,
+    #[serde(default)]
+    pub transform: Transform,
+    /// Material used instead of the one the geometry was defined with
+    #[serde(default)]
+    pub material_override: Option<Material>,
+}
+
+/// Scene graph node. Its objects and children are positioned relative to it.
+#[derive(Deserialize)]
+pub struct Node {
+    #[serde(default)]
+    pub transform: Transform,
+    #[serde(default)]
+    pub objects: Vec<Object>,
+    /// Names of `World::geometry` entries placed at the node
+    #[serde(default)]
+    pub geometry: Vec<String>,
+    /// Material used for everything in the node and its children, unless overridden again
+    #[serde(default)]
+    pub material_override: Option<Material>,
+    #[serde(default)]
+    pub children: Vec<Node>,
+}
+
+impl From<InstanceDef> for Node {
+    fn from(instance: InstanceDef) -> Self {
+        Self {
+            transform: instance.transform,
+            objects: Vec::new(),
+            geometry: vec![instance.geometry],
+            material_override: instance.material_override,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Object placed in the world
+pub struct Instance {
+    pub object: Arc<Object>,
+    pub transform: Transform,
+    /// Material used instead of the one the object was defined with
+    pub material_override: Option<Material>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
 pub struct Transform {
+    #[serde(default)]
     pub position: Vec3,
+    /// Unit quaternion
+    #[serde(default = "Transform::default_rotation")]
     pub rotation: Quat,
+    /// Uniform scale factor
+    #[serde(default = "Transform::default_scale")]
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: Vec3::default(),
+            rotation: Self::default_rotation(),
+            scale: Self::default_scale(),
+        }
+    }
+}
+
+impl Transform {
+    const fn default_rotation() -> Quat {
+        Quat::ONE
+    }
+
+    const fn default_scale() -> f32 {
+        1.0
+    }
+
+    /// Same transform with its rotation scaled back to a unit quaternion
+    #[must_use]
+    pub fn normalized(&self) -> Self {
+        Self {
+            rotation: self.rotation * self.rotation.mag().recip(),
+            ..*self
+        }
+    }
+
+    /// Transform of `child`, given relative to `self`, in the parent space of `self`
+    #[must_use]
+    pub fn compose(&self, child: &Self) -> Self {
+        Self {
+            position: self.point_to_world(child.position),
+            rotation: self.rotation * child.rotation,
+            scale: self.scale * child.scale,
+        }
+    }
+
+    /// Map a point from the local space of the transform to world space
+    pub fn point_to_world(&self, p: Vec3) -> Vec3 {
+        p.rotate(self.rotation) * self.scale + self.position
+    }
+
+    /// Map a point from world space to the local space of the transform
+    pub fn point_to_local(&self, p: Vec3) -> Vec3 {
+        (p - self.position).rotate(self.rotation.conj()) / self.scale
+    }
+
+    /// Map a direction from world space to the local space of the transform. Distances along a
+    /// ray stay the same when both its base and direction are mapped.
+    pub fn dir_to_local(&self, dir: Vec3) -> Vec3 {
+        dir.rotate(self.rotation.conj()) / self.scale
+    }
+
+    /// Map a normal from the local space of the transform to world space
+    pub fn normal_to_world(&self, normal: Vec3) -> Vec3 {
+        normal.rotate(self.rotation)
+    }
+
+    /// World space bounds of a local space box
+    pub fn bounds_to_world(&self, bounds: Aabb) -> Aabb {
+        let Aabb { min, max } = bounds;
+        // Infinite boxes would turn into NaNs when rotated
+        if [min.x, min.y, min.z, max.x, max.y, max.z]
+            .iter()
+            .any(|n| n.is_infinite())
+        {
+            return Aabb::INFINITE;
+        }
+        Aabb::from_points(
+            [
+                (min.x, min.y, min.z),
+                (min.x, min.y, max.z),
+                (min.x, max.y, min.z),
+                (min.x, max.y, max.z),
+                (max.x, min.y, min.z),
+                (max.x, min.y, max.z),
+                (max.x, max.y, min.z),
+                (max.x, max.y, max.z),
+            ]
+            .map(|(x, y, z)| self.point_to_world(Vec3::new(x, y, z))),
+        )
+    }
 }
 
 #[derive(Clone, Copy, Deserialize)]
@@ -47,6 +789,22 @@ impl std::ops::IndexMut<usize> for Color {
     }
 }
 
+impl std::ops::Mul for Color {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        #[allow(clippy::cast_possible_truncation)]
+        Self([0, 1, 2].map(|i| (self[i] as f32 * rhs[i] as f32 / 255.0).round() as u8))
+    }
+}
+
+impl std::ops::Add for Color {
+    type Output = Self;
+    /// Channel sum, saturating at white
+    fn add(self, rhs: Self) -> Self {
+        Self([0, 1, 2].map(|i| self[i].saturating_add(rhs[i])))
+    }
+}
+
 impl std::ops::Mul<f32> for Color {
     type Output = Self;
     fn mul(self, rhs: f32) -> Self {
@@ -57,12 +815,168 @@ impl std::ops::Mul<f32> for Color {
 
 impl Color {
     pub const BLACK: Self = Self([0; 3]);
+    pub const WHITE: Self = Self([255; 3]);
+
+    const fn black() -> Self {
+        Self::BLACK
+    }
+
+    const fn white() -> Self {
+        Self::WHITE
+    }
+
+    /// Channels scaled to `0.0..=1.0`
+    pub fn to_rgb(self) -> [f32; 3] {
+        self.0.map(|n| f32::from(n) / 255.0)
+    }
 
-    #[allow(dead_code)]
-    fn interpolate(self, rhs: Self, ratio: f32) -> Self {
+    /// Color from channels in `0.0..=1.0`, clamping brighter ones
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn from_rgb(rgb: [f32; 3]) -> Self {
+        Self(rgb.map(|n| (n * 255.0).round().clamp(0.0, 255.0) as u8))
+    }
+
+    /// Perceived brightness, from `0.0` for black to `1.0` for white
+    pub fn luminance(self) -> f32 {
+        let [r, g, b] = self.0.map(f32::from);
+        (0.2126 * r + 0.7152 * g + 0.0722 * b) / 255.0
+    }
+
+    pub fn interpolate(self, rhs: Self, ratio: f32) -> Self {
+        // Rounded once, rounding both terms separately can overflow
         #[allow(clippy::cast_possible_truncation)]
-        Self([0, 1, 2].map(|i| {
-            (self[i] as f32 * (1.0 - ratio)).round() as u8 + (rhs[i] as f32 * ratio).round() as u8
-        }))
+        Self(
+            [0, 1, 2]
+                .map(|i| (self[i] as f32 * (1.0 - ratio) + rhs[i] as f32 * ratio).round() as u8),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Rng;
+
+    #[test]
+    fn area_light_samples() {
+        let target = Vec3::new(0.0, 0.0, 5.0);
+        let mut rng = Rng::from_point(target);
+        let center = Vec3::new(1.0, 2.0, 3.0);
+        let sphere = AreaLight::Sphere(center, 0.5, Color::WHITE, 1.0);
+        let [p1, p2, p3] = [Vec3::I, Vec3::J, Vec3::K];
+        let triangle = AreaLight::Triangle([p1, p2, p3], Color::WHITE, 1.0);
+        for _ in 0..100 {
+            let uv = (rng.next_f32(), rng.next_f32());
+
+            let (point, normal, _) = sphere.sample(target, uv);
+            assert!(((point - center).mag() - 0.5).abs() < 1e-5);
+            assert!((target - center).dot(normal) >= -1e-5);
+
+            // Inside the triangle when the barycentric areas add up to the whole
+            let (point, _, area) = triangle.sample(target, uv);
+            let sub_areas: f32 = [(p1, p2), (p2, p3), (p3, p1)]
+                .iter()
+                .map(|&(a, b)| (a - point).cross(b - point).mag() / 2.0)
+                .sum();
+            assert!((sub_areas - area).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn gradient_background() {
+        let sky = Background::Gradient(Color([200; 3]), Color([0, 0, 100]));
+        let color = |dir| Color::from_rgb(sky.radiance(dir)).0;
+        assert_eq!(color(Vec3::new(0.0, 1.0, 0.0)), [200; 3]);
+        assert_eq!(color(Vec3::new(0.0, 1.0, -1.0)), [200; 3]);
+        assert_eq!(color(Vec3::new(0.0, 0.0, 3.0)), [0, 0, 100]);
+    }
+
+    #[test]
+    fn compose_transforms() {
+        let parent = Transform {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            rotation: Quat::rotation(Vec3::K, std::f32::consts::FRAC_PI_2),
+            scale: 2.0,
+        };
+        let child = Transform {
+            position: Vec3::new(0.5, 0.0, -1.0),
+            rotation: Quat::rotation(Vec3::I, 0.3),
+            scale: 0.5,
+        };
+        let composed = parent.compose(&child);
+
+        let p = Vec3::new(0.3, -0.7, 1.1);
+        let expected = parent.point_to_world(child.point_to_world(p));
+        assert!((composed.point_to_world(p) - expected).sq_mag() < 1e-10);
+        assert!((composed.point_to_local(expected) - p).sq_mag() < 1e-10);
+    }
+
+    #[test]
+    fn world_bounds_skip_unbounded() {
+        let material = || Material::plain(Color::WHITE);
+        let row = Sdf::Repeat(Vec3::I, Box::new(Sdf::Sphere(0.2)));
+        let world = World {
+            objects: vec![
+                Object::Sphere(Vec3::new(1.0, 2.0, 3.0), 1.0, material()),
+                Object::Sdf(row, material()),
+            ],
+            ..World::default()
+        };
+        let bounds = world.bounds();
+        assert!(bounds.is_finite());
+        assert!((bounds.centroid() - Vec3::new(1.0, 2.0, 3.0)).mag() < 1e-5);
+        assert!(!World::default().bounds().is_finite());
+    }
+
+    #[test]
+    fn degenerate_transforms() {
+        let load = |transform: &str| {
+            World::load(&format!(
+                "World(objects: [], instances: [Instance(geometry: \"none\", transform: {transform})])"
+            ))
+        };
+        assert!(matches!(
+            load("Transform(scale: 0.0)"),
+            Err(LoadError::DegenerateTransform(_))
+        ));
+        assert!(matches!(
+            load("Transform(scale: -1.0)"),
+            Err(LoadError::DegenerateTransform(_))
+        ));
+        assert!(matches!(
+            load("Transform(rotation: Quat(r: 0.0, i: 0.0, j: 0.0, k: 0.0))"),
+            Err(LoadError::DegenerateTransform(_))
+        ));
+        // Otherwise fine transforms get as far as looking up the geometry
+        assert!(matches!(
+            load("Transform(scale: 2.0)"),
+            Err(LoadError::UnknownGeometry(_))
+        ));
+    }
+
+    #[test]
+    fn shipped_scenes_load() {
+        let scenes = [
+            ("amogus", include_str!("../scenes/amogus.ron")),
+            ("board", include_str!("../scenes/board.ron")),
+            ("cube", include_str!("../scenes/cube.ron")),
+            ("sample", include_str!("../scenes/sample.ron")),
+            ("sdf", include_str!("../scenes/sdf.ron")),
+            ("mesh", include_str!("../scenes/mesh.ron")),
+            ("instances", include_str!("../scenes/instances.ron")),
+            ("scene_graph", include_str!("../scenes/scene_graph.ron")),
+            ("textured", include_str!("../scenes/textured.ron")),
+            ("procedural", include_str!("../scenes/procedural.ron")),
+            ("bump", include_str!("../scenes/bump.ron")),
+            ("volume", include_str!("../scenes/volume.ron")),
+            ("emissive", include_str!("../scenes/emissive.ron")),
+            ("pbr", include_str!("../scenes/pbr.ron")),
+            ("glass", include_str!("../scenes/glass.ron")),
+        ];
+        for (name, src) in scenes {
+            if let Err(err) = World::load(src) {
+                panic!("scenes/{name}.ron: {err}");
+            }
+        }
     }
 }